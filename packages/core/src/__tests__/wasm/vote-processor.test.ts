@@ -0,0 +1,107 @@
+import type { VoteData } from '../../wasm/vote-processor';
+
+// Both backends are build outputs (see src/wasm/README.md), so they are
+// mocked here at the paths vote-processor.ts loads them from.
+const NATIVE_ADDON = '../../wasm/vote_processor.node';
+const WASM_GLUE = '../../wasm/pkg/vote_processor.js';
+
+const result = {
+  approved: 150n,
+  rejected: 0n,
+  voters: ['alice'],
+  duplicates: [],
+};
+
+const votes: VoteData[] = [{ balance: '150', approved: 1, voter: 'alice' }];
+
+function loadProcessor(): typeof import('../../wasm/vote-processor') {
+  let module!: typeof import('../../wasm/vote-processor');
+  jest.isolateModules(() => {
+    module = require('../../wasm/vote-processor');
+  });
+  return module;
+}
+
+describe('WasmVoteProcessor', () => {
+  afterEach(() => {
+    jest.resetModules();
+    jest.dontMock(NATIVE_ADDON);
+    jest.dontMock(WASM_GLUE);
+  });
+
+  it('uses the native addon under Node.js', async () => {
+    const processVoteBytes = jest.fn().mockReturnValue(result);
+    const init = jest.fn();
+    jest.doMock(
+      NATIVE_ADDON,
+      () => ({
+        VoteProcessor: jest.fn().mockImplementation(() => ({ processVoteBytes })),
+      }),
+      { virtual: true },
+    );
+    jest.doMock(WASM_GLUE, () => ({ __esModule: true, default: init }), {
+      virtual: true,
+    });
+
+    const { WasmVoteProcessor } = loadProcessor();
+    const processor = await WasmVoteProcessor.create();
+
+    await expect(processor.processVoteChunk(votes)).resolves.toEqual(result);
+    expect(Buffer.isBuffer(processVoteBytes.mock.calls[0][0])).toBe(true);
+    expect(init).not.toHaveBeenCalled();
+  });
+
+  it('falls back to the wasm-bindgen glue without the addon', async () => {
+    const processVoteBytes = jest.fn().mockReturnValue(result);
+    const free = jest.fn();
+    const init = jest.fn().mockResolvedValue(undefined);
+    jest.doMock(
+      NATIVE_ADDON,
+      () => {
+        throw new Error('Cannot find module');
+      },
+      { virtual: true },
+    );
+    jest.doMock(
+      WASM_GLUE,
+      () => ({
+        __esModule: true,
+        default: init,
+        WasmVoteProcessor: jest.fn().mockImplementation(() => ({
+          process_vote_bytes: processVoteBytes,
+          free,
+        })),
+      }),
+      { virtual: true },
+    );
+
+    const { WasmVoteProcessor } = loadProcessor();
+    const processor = await WasmVoteProcessor.create();
+
+    await expect(processor.processVoteChunk(votes)).resolves.toEqual(result);
+    expect(init).toHaveBeenCalledTimes(1);
+    expect(processVoteBytes.mock.calls[0][0]).toBeInstanceOf(Uint8Array);
+    await processor.dispose();
+    expect(free).toHaveBeenCalledTimes(1);
+  });
+
+  it('reports a missing wasm build', async () => {
+    jest.doMock(
+      NATIVE_ADDON,
+      () => {
+        throw new Error('Cannot find module');
+      },
+      { virtual: true },
+    );
+    jest.doMock(
+      WASM_GLUE,
+      () => {
+        throw new Error('Cannot find module');
+      },
+      { virtual: true },
+    );
+
+    const { WasmVoteProcessor, WasmError } = loadProcessor();
+    await expect(WasmVoteProcessor.create()).rejects.toBeInstanceOf(WasmError);
+  });
+});
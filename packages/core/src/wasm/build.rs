@@ -0,0 +1,5 @@
+fn main() {
+    // Link settings for the Node-API addon.
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
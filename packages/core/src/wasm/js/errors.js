@@ -0,0 +1,14 @@
+// Error thrown by the vote processor (see `VoteError` in src/error.rs).
+//
+// `code` is a stable identifier such as `PARSE_ERROR` or `DUPLICATE_VOTE`.
+// `index` and `voter` identify the failing vote when there is one and are
+// `undefined` otherwise.
+export class VoteProcessorError extends Error {
+  constructor(message, code, index, voter) {
+    super(message);
+    this.name = 'VoteProcessorError';
+    this.code = code;
+    this.index = index;
+    this.voter = voter;
+  }
+}
@@ -0,0 +1,494 @@
+//! Running tally across many chunks of the same voting period.
+//!
+//! [`VoteProcessor::process_chunk`] only deduplicates within one chunk. The
+//! accumulator keeps the counted contribution of every voter it has seen, so
+//! the [`DuplicatePolicy`] also applies across chunk boundaries and a full
+//! `MAX_VOTES_PER_PERIOD` period can be tallied incrementally.
+
+use crate::decode::decode_votes;
+use crate::details::{VoterChoice, VoterDetail};
+use crate::duplicates::{DuplicatePolicy, DuplicateVoter};
+use crate::error::VoteError;
+use crate::processor::{try_map_in_order, ChunkResult, VoteData, VoteProcessor};
+use crate::tally::VoteTally;
+use std::collections::HashMap;
+
+/// The votes currently counted for one voter.
+#[derive(Debug, Clone)]
+struct VoterEntry {
+    approved: u128,
+    rejected: u128,
+    /// Votes cast, counted or not.
+    vote_count: usize,
+    /// Sum of the balances of the counted votes.
+    balance: u128,
+    choice: VoterChoice,
+    /// Global index of the voter's first vote.
+    first_index: usize,
+    /// `(timestamp, global index)` of the vote counted under
+    /// [`DuplicatePolicy::LastWins`]. A missing timestamp is taken as `0`,
+    /// older than any vote that has one.
+    latest: (u64, usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct VoteTallyAccumulator {
+    processor: VoteProcessor,
+    approved: u128,
+    rejected: u128,
+    /// Number of votes added so far; the next vote gets this global index.
+    vote_count: usize,
+    voters: HashMap<String, VoterEntry>,
+    /// Global indices of every vote from voters that voted more than once.
+    duplicates: HashMap<String, Vec<usize>>,
+}
+
+impl VoteTallyAccumulator {
+    pub fn new(processor: VoteProcessor) -> Self {
+        VoteTallyAccumulator {
+            processor,
+            approved: 0,
+            rejected: 0,
+            vote_count: 0,
+            voters: HashMap::new(),
+            duplicates: HashMap::new(),
+        }
+    }
+
+    /// Number of votes added so far, counted or not.
+    pub fn vote_count(&self) -> usize {
+        self.vote_count
+    }
+
+    /// Adds a chunk of votes. Vote indices in the duplicate report are global
+    /// across all chunks added to this accumulator.
+    ///
+    /// On error the accumulator is left unchanged.
+    pub fn add_chunk(&mut self, votes: &[VoteData]) -> Result<(), VoteError> {
+        self.processor.check_chunk_size(votes.len())?;
+        let offset = self.vote_count;
+        let weights = try_map_in_order(votes, |i, vote| {
+            self.processor.vote_weight(offset + i, vote)
+        })?;
+
+        if self.processor.duplicate_policy() == DuplicatePolicy::Reject {
+            let mut seen = HashMap::new();
+            for (i, vote) in votes.iter().enumerate() {
+                let first = match self.voters.get(&vote.voter) {
+                    Some(entry) => Some(entry.first_index),
+                    None => seen.insert(vote.voter.as_str(), offset + i),
+                };
+                if let Some(first) = first {
+                    return Err(VoteError::Duplicate {
+                        voter: vote.voter.clone(),
+                        indices: vec![first, offset + i],
+                    });
+                }
+            }
+        }
+
+        // Replacing or skipping votes never adds more than the chunk's gross
+        // weight, so checking that up front keeps the update below infallible.
+        let (mut gross_approved, mut gross_rejected) = (self.approved, self.rejected);
+        for (i, (vote, weight)) in votes.iter().zip(&weights).enumerate() {
+            let overflow = |tally: &str| VoteError::Overflow {
+                tally: tally.to_string(),
+                index: Some(offset + i),
+                voter: Some(vote.voter.clone()),
+            };
+            gross_approved = gross_approved
+                .checked_add(weight.approved)
+                .ok_or_else(|| overflow("approved"))?;
+            gross_rejected = gross_rejected
+                .checked_add(weight.rejected)
+                .ok_or_else(|| overflow("rejected"))?;
+        }
+
+        for (vote, weight) in votes.iter().zip(weights) {
+            let index = self.vote_count;
+            self.vote_count += 1;
+            let entry = VoterEntry {
+                approved: weight.approved,
+                rejected: weight.rejected,
+                vote_count: 1,
+                balance: weight.balance,
+                choice: VoterChoice::of(vote.approved),
+                first_index: index,
+                latest: (vote.timestamp.unwrap_or(0), index),
+            };
+            self.insert(vote.voter.clone(), entry, &[index]);
+        }
+        Ok(())
+    }
+
+    /// Decodes votes in the `serializeVotes` binary layout and adds them.
+    pub fn add_bytes(&mut self, bytes: &[u8]) -> Result<(), VoteError> {
+        let votes = decode_votes(bytes)?;
+        self.add_chunk(&votes)
+    }
+
+    /// Merges another accumulator's votes into this one, as if they had been
+    /// added after this accumulator's own votes. Both accumulators must use
+    /// the same processor settings.
+    ///
+    /// On error the accumulator is left unchanged.
+    pub fn merge(&mut self, other: VoteTallyAccumulator) -> Result<(), VoteError> {
+        if self.processor.tally_mode() != other.processor.tally_mode()
+            || self.processor.duplicate_policy() != other.processor.duplicate_policy()
+            || self.processor.weighting() != other.processor.weighting()
+        {
+            return Err(VoteError::SettingsMismatch);
+        }
+        if self.processor.duplicate_policy() == DuplicatePolicy::Reject {
+            // Report the conflict that comes first in merged order.
+            let conflict = other
+                .voters
+                .iter()
+                .filter_map(|(voter, entry)| Some((voter, self.voters.get(voter)?, entry)))
+                .min_by_key(|(_, _, entry)| entry.first_index);
+            if let Some((voter, existing, entry)) = conflict {
+                return Err(VoteError::Duplicate {
+                    voter: voter.clone(),
+                    indices: vec![existing.first_index, self.vote_count + entry.first_index],
+                });
+            }
+        }
+        self.check_headroom(other.approved, other.rejected)?;
+
+        let offset = self.vote_count;
+        self.vote_count += other.vote_count;
+        let mut other_duplicates = other.duplicates;
+        for (voter, mut entry) in other.voters {
+            entry.first_index += offset;
+            entry.latest.1 += offset;
+            let indices = match other_duplicates.remove(&voter) {
+                Some(indices) => indices.into_iter().map(|i| i + offset).collect(),
+                None => vec![entry.first_index],
+            };
+            self.insert(voter, entry, &indices);
+        }
+        Ok(())
+    }
+
+    /// Current tally, leaving the accumulator usable.
+    pub fn snapshot(&self) -> ChunkResult {
+        let mut duplicates: Vec<(usize, DuplicateVoter)> = self
+            .duplicates
+            .iter()
+            .map(|(voter, indices)| {
+                let mut indices = indices.clone();
+                indices.sort_unstable();
+                (
+                    indices[0],
+                    DuplicateVoter {
+                        voter: voter.clone(),
+                        indices,
+                    },
+                )
+            })
+            .collect();
+        duplicates.sort_unstable_by_key(|(first, _)| *first);
+
+        let mut voters: Vec<(&String, &VoterEntry)> = self.voters.iter().collect();
+        voters.sort_unstable_by_key(|(voter, _)| *voter);
+        let details = self.processor.detailed().then(|| {
+            voters
+                .iter()
+                .map(|(voter, entry)| VoterDetail {
+                    voter: voter.to_string(),
+                    vote_count: entry.vote_count,
+                    balance: entry.balance,
+                    power: entry.approved + entry.rejected,
+                    choice: entry.choice,
+                })
+                .collect()
+        });
+
+        ChunkResult {
+            approved: self.approved,
+            rejected: self.rejected,
+            voters: voters.into_iter().map(|(voter, _)| voter.clone()).collect(),
+            duplicates: duplicates.into_iter().map(|(_, d)| d).collect(),
+            details,
+        }
+    }
+
+    /// Final tally, consuming the accumulator.
+    pub fn finalize(self) -> ChunkResult {
+        self.snapshot()
+    }
+
+    /// Current tally as the consensus layer's [`VoteTally`], with
+    /// participation measured against `eligible_voters`.
+    pub fn tally(&self, eligible_voters: u64, timestamp: u64) -> VoteTally {
+        VoteTally::from_result(
+            &self.snapshot(),
+            self.processor.duplicate_policy(),
+            self.vote_count,
+            eligible_voters,
+            timestamp,
+        )
+    }
+
+    fn check_headroom(&self, approved: u128, rejected: u128) -> Result<(), VoteError> {
+        let overflow = |tally: &str| VoteError::Overflow {
+            tally: tally.to_string(),
+            index: None,
+            voter: None,
+        };
+        self.approved
+            .checked_add(approved)
+            .ok_or_else(|| overflow("approved"))?;
+        self.rejected
+            .checked_add(rejected)
+            .ok_or_else(|| overflow("rejected"))?;
+        Ok(())
+    }
+
+    /// Records `incoming` for `voter`, whose votes have the global `indices`,
+    /// resolving any earlier entry with the duplicate policy. Callers must
+    /// have checked headroom and, for [`DuplicatePolicy::Reject`], conflicts.
+    fn insert(&mut self, voter: String, incoming: VoterEntry, indices: &[usize]) {
+        let policy = self.processor.duplicate_policy();
+        let Some(existing) = self.voters.get_mut(&voter) else {
+            self.approved += incoming.approved;
+            self.rejected += incoming.rejected;
+            if indices.len() > 1 {
+                self.duplicates.insert(voter.clone(), indices.to_vec());
+            }
+            self.voters.insert(voter, incoming);
+            return;
+        };
+
+        existing.vote_count += incoming.vote_count;
+        match policy {
+            DuplicatePolicy::Sum => {
+                existing.approved += incoming.approved;
+                existing.rejected += incoming.rejected;
+                existing.balance += incoming.balance;
+                existing.choice = existing.choice.combine(incoming.choice);
+                self.approved += incoming.approved;
+                self.rejected += incoming.rejected;
+            }
+            DuplicatePolicy::LastWins if incoming.latest > existing.latest => {
+                self.approved = self.approved - existing.approved + incoming.approved;
+                self.rejected = self.rejected - existing.rejected + incoming.rejected;
+                existing.approved = incoming.approved;
+                existing.rejected = incoming.rejected;
+                existing.balance = incoming.balance;
+                existing.choice = incoming.choice;
+                existing.latest = incoming.latest;
+            }
+            _ => {}
+        }
+
+        let first_index = existing.first_index;
+        self.duplicates
+            .entry(voter)
+            .or_insert_with(|| vec![first_index])
+            .extend_from_slice(indices);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote(balance: &str, approved: i32, voter: &str, timestamp: u64) -> VoteData {
+        VoteData {
+            balance: balance.to_string(),
+            approved,
+            voter: voter.to_string(),
+            timestamp: Some(timestamp),
+        }
+    }
+
+    fn processor(policy: DuplicatePolicy) -> VoteProcessor {
+        let mut processor = VoteProcessor::new();
+        processor.set_duplicate_policy(policy);
+        processor
+    }
+
+    #[test]
+    fn single_chunk_matches_process_chunk() {
+        let votes = vec![
+            vote("10", 1, "a", 1),
+            vote("5", 0, "b", 1),
+            vote("7", 0, "a", 2),
+        ];
+        for policy in [
+            DuplicatePolicy::Sum,
+            DuplicatePolicy::FirstWins,
+            DuplicatePolicy::LastWins,
+        ] {
+            let mut processor = processor(policy);
+            processor.set_detailed(true);
+            let mut acc = processor.accumulator();
+            acc.add_chunk(&votes).unwrap();
+            let expected = processor.process_chunk(&votes).unwrap();
+            assert_eq!(acc.finalize(), expected, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn deduplicates_across_chunks() {
+        let mut acc = processor(DuplicatePolicy::LastWins).accumulator();
+        acc.add_chunk(&[vote("10", 1, "a", 5), vote("3", 1, "b", 5)])
+            .unwrap();
+        acc.add_chunk(&[vote("4", 0, "a", 9)]).unwrap();
+
+        let result = acc.snapshot();
+        assert_eq!((result.approved, result.rejected), (3, 4));
+        assert_eq!(result.duplicates[0].indices, vec![0, 2]);
+
+        // An older vote in a later chunk does not replace the newer one.
+        acc.add_chunk(&[vote("100", 1, "a", 1)]).unwrap();
+        let result = acc.finalize();
+        assert_eq!((result.approved, result.rejected), (3, 4));
+        assert_eq!(result.duplicates[0].indices, vec![0, 2, 3]);
+        assert_eq!(result.voters, vec!["a", "b"]);
+        assert_eq!(result.details, None);
+    }
+
+    #[test]
+    fn untimed_votes_in_later_chunks_do_not_win() {
+        let mut acc = processor(DuplicatePolicy::LastWins).accumulator();
+        acc.add_chunk(&[vote("10", 1, "a", 5)]).unwrap();
+        let mut untimed = vote("4", 0, "a", 0);
+        untimed.timestamp = None;
+        acc.add_chunk(&[untimed]).unwrap();
+
+        let result = acc.finalize();
+        assert_eq!((result.approved, result.rejected), (10, 0));
+        assert_eq!(result.duplicates[0].indices, vec![0, 1]);
+    }
+
+    #[test]
+    fn details_follow_the_policy_across_chunks() {
+        let mut processor = processor(DuplicatePolicy::LastWins);
+        processor.set_detailed(true);
+        let mut acc = processor.accumulator();
+        acc.add_chunk(&[vote("10", 1, "a", 5)]).unwrap();
+        acc.add_chunk(&[vote("4", 0, "a", 9), vote("100", 1, "a", 1)])
+            .unwrap();
+
+        let details = acc.snapshot().details.unwrap();
+        assert_eq!(
+            details,
+            vec![VoterDetail {
+                voter: "a".to_string(),
+                vote_count: 3,
+                balance: 4,
+                power: 4,
+                choice: VoterChoice::Reject,
+            }]
+        );
+    }
+
+    #[test]
+    fn reject_policy_leaves_state_unchanged() {
+        let mut acc = processor(DuplicatePolicy::Reject).accumulator();
+        acc.add_chunk(&[vote("10", 1, "a", 0)]).unwrap();
+        let err = acc
+            .add_chunk(&[vote("1", 1, "b", 0), vote("1", 1, "a", 0)])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            VoteError::Duplicate {
+                voter: "a".to_string(),
+                indices: vec![0, 2],
+            }
+        );
+
+        let result = acc.snapshot();
+        assert_eq!(acc.vote_count(), 1);
+        assert_eq!(result.approved, 10);
+        assert_eq!(result.voters, vec!["a"]);
+    }
+
+    #[test]
+    fn merge_offsets_indices_and_applies_policy() {
+        let processor = processor(DuplicatePolicy::FirstWins);
+        let mut left = processor.accumulator();
+        left.add_chunk(&[vote("10", 1, "a", 0), vote("2", 0, "b", 0)])
+            .unwrap();
+        let mut right = processor.accumulator();
+        right
+            .add_chunk(&[vote("6", 0, "c", 0), vote("50", 0, "a", 0)])
+            .unwrap();
+
+        left.merge(right).unwrap();
+        let result = left.finalize();
+        assert_eq!((result.approved, result.rejected), (10, 8));
+        assert_eq!(
+            result.duplicates,
+            vec![DuplicateVoter {
+                voter: "a".into(),
+                indices: vec![0, 3]
+            }]
+        );
+        assert_eq!(result.voters, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn tally_counts_votes_across_chunks() {
+        let mut acc = processor(DuplicatePolicy::Sum).accumulator();
+        acc.add_chunk(&[vote("1", 1, "a", 0), vote("1", 0, "b", 0)])
+            .unwrap();
+        acc.add_chunk(&[vote("1", 1, "a", 0)]).unwrap();
+
+        let tally = acc.tally(10, 99);
+        assert_eq!((tally.approved, tally.rejected), (2, 1));
+        assert_eq!((tally.total_votes, tally.unique_voters), (3, 2));
+        assert_eq!(tally.participation_rate, 0.2);
+        assert_eq!(tally.timestamp, 99);
+    }
+
+    #[test]
+    fn merge_reports_first_conflict_under_reject() {
+        let processor = processor(DuplicatePolicy::Reject);
+        let mut left = processor.accumulator();
+        left.add_chunk(&[vote("1", 1, "a", 0), vote("1", 1, "b", 0)])
+            .unwrap();
+        let mut right = processor.accumulator();
+        right
+            .add_chunk(&[
+                vote("1", 1, "c", 0),
+                vote("1", 1, "b", 0),
+                vote("1", 1, "a", 0),
+            ])
+            .unwrap();
+
+        let err = left.merge(right).unwrap_err();
+        assert_eq!(
+            err,
+            VoteError::Duplicate {
+                voter: "b".to_string(),
+                indices: vec![1, 3],
+            }
+        );
+        assert_eq!(left.vote_count(), 2);
+    }
+
+    #[test]
+    fn merge_requires_matching_settings() {
+        let mut left = processor(DuplicatePolicy::Sum).accumulator();
+        let right = processor(DuplicatePolicy::FirstWins).accumulator();
+        assert_eq!(left.merge(right), Err(VoteError::SettingsMismatch));
+    }
+
+    #[test]
+    fn overflow_leaves_state_unchanged() {
+        let mut acc = VoteProcessor::new().accumulator();
+        acc.add_chunk(&[vote("1", 1, "a", 0)]).unwrap();
+        // Unreachable with in-supply balances alone; start near the limit.
+        acc.approved = u128::MAX;
+        let err = acc.add_chunk(&[vote("1", 1, "b", 0)]).unwrap_err();
+        assert_eq!(
+            (err.code(), err.index(), err.voter()),
+            ("OVERFLOW", Some(1), Some("b"))
+        );
+        assert_eq!(acc.vote_count(), 1);
+        assert_eq!(acc.snapshot().approved, u128::MAX);
+    }
+}
@@ -0,0 +1,164 @@
+//! Strict parsing of TAG amounts in base units.
+//!
+//! Balances and chain-vote amounts arrive as strings (`bigint` does not
+//! survive JSON). Only canonical non-negative integers are accepted: plain
+//! decimal without sign, exponent, fraction or leading zeros, or `0x` hex
+//! without leading zeros. Values above the TAG supply are rejected, so a
+//! malformed vote cannot swamp a tally.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// `TRANSACTION.AMOUNT_LIMITS.MAX`: the 50,000,000 TAG `MAX_SUPPLY` in base
+/// units (8 decimals).
+pub const MAX_AMOUNT: u128 = 5_000_000_000_000_000;
+
+/// Why an amount string was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AmountError {
+    Empty,
+    Negative,
+    /// A character other than a digit, e.g. `+`, `.`, `e` or whitespace.
+    InvalidDigit,
+    /// A leading `0` on a non-zero value, in decimal or hex.
+    LeadingZero,
+    /// More than [`MAX_AMOUNT`].
+    ExceedsMaxSupply,
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AmountError::Empty => "empty amount",
+            AmountError::Negative => "negative amount",
+            AmountError::InvalidDigit => "not an integer",
+            AmountError::LeadingZero => "leading zeros are not allowed",
+            AmountError::ExceedsMaxSupply => "exceeds the maximum TAG supply",
+        })
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+/// An amount of TAG in base units, at most [`MAX_AMOUNT`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u128);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn new(value: u128) -> Result<Self, AmountError> {
+        if value > MAX_AMOUNT {
+            return Err(AmountError::ExceedsMaxSupply);
+        }
+        Ok(Amount(value))
+    }
+
+    pub fn get(self) -> u128 {
+        self.0
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (digits, radix) = match s.strip_prefix("0x") {
+            Some(hex) => (hex, 16),
+            None => (s, 10),
+        };
+        if digits.is_empty() {
+            return Err(AmountError::Empty);
+        }
+        if digits.starts_with('-') {
+            return Err(AmountError::Negative);
+        }
+        if !digits.chars().all(|c| c.is_digit(radix)) {
+            return Err(AmountError::InvalidDigit);
+        }
+        if digits.len() > 1 && digits.starts_with('0') {
+            return Err(AmountError::LeadingZero);
+        }
+
+        // Only digits remain, so the sole failure is a value past `u128`.
+        let value =
+            u128::from_str_radix(digits, radix).map_err(|_| AmountError::ExceedsMaxSupply)?;
+        Amount::new(value)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Serialized as a decimal string, like the TypeScript `bigint` fields.
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<u128, AmountError> {
+        s.parse::<Amount>().map(Amount::get)
+    }
+
+    #[test]
+    fn accepts_canonical_integers() {
+        assert_eq!(parse("0"), Ok(0));
+        assert_eq!(parse("1234"), Ok(1234));
+        assert_eq!(parse("0x0"), Ok(0));
+        assert_eq!(parse("0xff"), Ok(255));
+        assert_eq!(parse("0xFF"), Ok(255));
+        assert_eq!(parse(&MAX_AMOUNT.to_string()), Ok(MAX_AMOUNT));
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        assert_eq!(parse(""), Err(AmountError::Empty));
+        assert_eq!(parse("0x"), Err(AmountError::Empty));
+        assert_eq!(parse("-5"), Err(AmountError::Negative));
+        for s in ["inf", "NaN", "1e300", "1.5", "+5", " 5", "5 ", "0b1", "0xg"] {
+            assert_eq!(parse(s), Err(AmountError::InvalidDigit), "{s}");
+        }
+        assert_eq!(parse("007"), Err(AmountError::LeadingZero));
+        assert_eq!(parse("0x01"), Err(AmountError::LeadingZero));
+    }
+
+    #[test]
+    fn enforces_supply_limit() {
+        assert_eq!(
+            parse(&(MAX_AMOUNT + 1).to_string()),
+            Err(AmountError::ExceedsMaxSupply)
+        );
+        assert_eq!(
+            parse(&u128::MAX.to_string()),
+            Err(AmountError::ExceedsMaxSupply)
+        );
+        assert_eq!(
+            parse("1000000000000000000000000000000000000000000"),
+            Err(AmountError::ExceedsMaxSupply)
+        );
+    }
+
+    #[test]
+    fn round_trips_through_serde() {
+        let amount: Amount = serde_json::from_str("\"0x10\"").unwrap();
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "\"16\"");
+        assert!(serde_json::from_str::<Amount>("\"1.0\"").is_err());
+    }
+}
@@ -0,0 +1,522 @@
+//! `vote-tally`: recomputes a voting period's result offline from exported
+//! votes, with the same tally, validation and voting-power code the node
+//! runs through `WasmVoteProcessor`.
+//!
+//! Votes are read from the given files in order, or from stdin, as a JSON
+//! array, NDJSON (one vote per line) or the binary `serializeVotes` layout.
+//! JSON votes are `VoteData` records (`balance`, `approved`, `voter`,
+//! `timestamp`) and may also carry the `voteId`, `signature` and
+//! `chainVoteData` fields of an exported `Vote`. The result is printed to
+//! stdout as:
+//!
+//! ```text
+//! { "tally": VoteTally, "merkleRoot": "..." | null, "rejected": [RejectedVote] }
+//! ```
+//!
+//! `merkleRoot` covers the tallied votes and is `null` when any of them has
+//! no `voteId` or `timestamp`, as is always the case for binary input.
+
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use vote_processor::decode::decode_votes;
+use vote_processor::merkle;
+use vote_processor::validation::{self, ChainVoteData, RejectedVote, ValidatorStatus};
+use vote_processor::{
+    CandidateVote, DuplicatePolicy, MerkleVote, TallyMode, ValidatorSet, VoteData, VoteError,
+    VoteProcessor, VoteTally, VoterDetail,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// By file extension (`.json`, `.ndjson`, `.jsonl`, `.bin`), otherwise
+    /// by the first non-blank byte: `[` for JSON, `{` for NDJSON, anything
+    /// else for binary.
+    Auto,
+    Json,
+    Ndjson,
+    Binary,
+}
+
+/// Recompute a voting period's tally and votes Merkle root from exported
+/// votes.
+#[derive(Debug, Parser)]
+#[command(name = "vote-tally", version)]
+struct Args {
+    /// Vote files, read in order. Reads stdin when none are given or for `-`.
+    files: Vec<PathBuf>,
+
+    #[arg(long, value_enum, default_value_t = Format::Auto)]
+    format: Format,
+
+    /// `balance` or `quadratic`.
+    #[arg(long, default_value = "balance")]
+    tally_mode: TallyMode,
+
+    /// `reject`, `first-wins`, `last-wins` or `sum`.
+    #[arg(long, default_value = "first-wins")]
+    duplicate_policy: DuplicatePolicy,
+
+    /// Eligible voters, for the participation rate.
+    #[arg(long, default_value_t = 0)]
+    eligible_voters: u64,
+
+    /// `timestamp` of the tally in milliseconds. Fixed rather than the
+    /// current time so that runs are reproducible.
+    #[arg(long, default_value_t = 0)]
+    timestamp: u64,
+
+    /// JSON array of `{ address, isActive }` records. Votes that break the
+    /// `_verifyVote` rules are dropped and listed under `rejected`.
+    #[arg(long, requires = "now")]
+    validators: Option<PathBuf>,
+
+    /// Time in milliseconds at which vote age is measured.
+    #[arg(long, requires = "validators")]
+    now: Option<u64>,
+
+    /// Add a `voters` breakdown: vote count, balance, power and choice per
+    /// voter.
+    #[arg(long)]
+    detailed: bool,
+
+    /// Indent the output.
+    #[arg(long)]
+    pretty: bool,
+}
+
+/// A vote as exported by the node: the `VoteData` fields the processor
+/// tallies, plus the fields validation and the Merkle root need.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedVote {
+    balance: String,
+    approved: i32,
+    voter: String,
+    #[serde(default)]
+    timestamp: Option<u64>,
+    #[serde(default)]
+    vote_id: Option<String>,
+    #[serde(default)]
+    signature: String,
+    #[serde(default)]
+    chain_vote_data: Option<ChainVoteData>,
+}
+
+impl ExportedVote {
+    fn vote_data(&self) -> VoteData {
+        VoteData {
+            balance: self.balance.clone(),
+            approved: self.approved,
+            voter: self.voter.clone(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    fn candidate(&self) -> CandidateVote {
+        CandidateVote {
+            vote_id: self.vote_id.clone().unwrap_or_default(),
+            voter: self.voter.clone(),
+            signature: self.signature.clone(),
+            timestamp: self.timestamp.map(Into::into),
+            chain_vote_data: self.chain_vote_data.clone(),
+        }
+    }
+
+    fn merkle_vote(&self) -> Option<MerkleVote> {
+        Some(MerkleVote {
+            vote_id: self.vote_id.clone()?,
+            voter: self.voter.clone(),
+            timestamp: self.timestamp?,
+        })
+    }
+}
+
+impl From<VoteData> for ExportedVote {
+    fn from(vote: VoteData) -> Self {
+        ExportedVote {
+            balance: vote.balance,
+            approved: vote.approved,
+            voter: vote.voter,
+            timestamp: vote.timestamp,
+            vote_id: None,
+            signature: String::new(),
+            chain_vote_data: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+    tally: VoteTally,
+    merkle_root: Option<String>,
+    /// Per-voter breakdown, sorted by voter, with `--detailed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    voters: Option<Vec<VoterDetail>>,
+    /// Votes dropped by validation. Indices are positions in the input,
+    /// counted across all files.
+    rejected: Vec<RejectedVote>,
+}
+
+#[derive(Debug)]
+enum CliError {
+    /// A stream that could not be read, parsed or written. `source` is the
+    /// file path, `<stdin>` or `<stdout>`.
+    Input { source: String, message: String },
+    /// The tally failed. Vote indices count the votes left after
+    /// validation.
+    Vote(VoteError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Input { source, message } => write!(f, "{source}: {message}"),
+            CliError::Vote(error) => write!(f, "{}: {error}", error.code()),
+        }
+    }
+}
+
+impl From<VoteError> for CliError {
+    fn from(error: VoteError) -> Self {
+        CliError::Vote(error)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("vote-tally: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let paths: Vec<Option<&Path>> = if args.files.is_empty() {
+        vec![None]
+    } else {
+        args.files
+            .iter()
+            .map(|path| (path.as_os_str() != "-").then_some(path.as_path()))
+            .collect()
+    };
+
+    let mut votes = Vec::new();
+    for path in paths {
+        votes.extend(read_votes(path, args.format)?);
+    }
+
+    let validators = match (&args.validators, args.now) {
+        (Some(path), Some(now)) => Some((read_validators(path)?, now)),
+        _ => None,
+    };
+
+    let mut processor = VoteProcessor::new();
+    processor.set_tally_mode(args.tally_mode);
+    processor.set_duplicate_policy(args.duplicate_policy);
+    processor.set_detailed(args.detailed);
+    let report = tally(
+        &processor,
+        &votes,
+        validators.as_ref().map(|(set, now)| (set, *now)),
+        args.eligible_voters,
+        args.timestamp,
+    )?;
+
+    let output = if args.pretty {
+        serde_json::to_string_pretty(&report)
+    } else {
+        serde_json::to_string(&report)
+    }
+    .expect("Report serializes to JSON");
+    writeln!(io::stdout(), "{output}").map_err(|e| CliError::Input {
+        source: "<stdout>".to_string(),
+        message: e.to_string(),
+    })
+}
+
+fn source_name(path: Option<&Path>) -> String {
+    path.map_or_else(|| "<stdin>".to_string(), |p| p.display().to_string())
+}
+
+fn read_votes(path: Option<&Path>, format: Format) -> Result<Vec<ExportedVote>, CliError> {
+    let input_error = |message: String| CliError::Input {
+        source: source_name(path),
+        message,
+    };
+    let bytes = match path {
+        Some(path) => fs::read(path),
+        None => {
+            let mut bytes = Vec::new();
+            io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+        }
+    }
+    .map_err(|e| input_error(e.to_string()))?;
+
+    let format = match format {
+        Format::Auto => detect_format(path, &bytes),
+        format => format,
+    };
+    parse_votes(&bytes, format).map_err(input_error)
+}
+
+fn read_validators(path: &Path) -> Result<ValidatorSet, CliError> {
+    let input_error = |message: String| CliError::Input {
+        source: source_name(Some(path)),
+        message,
+    };
+    let bytes = fs::read(path).map_err(|e| input_error(e.to_string()))?;
+    let validators: Vec<ValidatorStatus> =
+        serde_json::from_slice(&bytes).map_err(|e| input_error(e.to_string()))?;
+    Ok(ValidatorSet::new(validators))
+}
+
+fn detect_format(path: Option<&Path>, bytes: &[u8]) -> Format {
+    let extension = path
+        .and_then(Path::extension)
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("json") => return Format::Json,
+        Some("ndjson" | "jsonl") => return Format::Ndjson,
+        Some("bin") => return Format::Binary,
+        _ => {}
+    }
+    match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'[') => Format::Json,
+        Some(b'{') => Format::Ndjson,
+        _ => Format::Binary,
+    }
+}
+
+/// Parses votes in a concrete `format`.
+fn parse_votes(bytes: &[u8], format: Format) -> Result<Vec<ExportedVote>, String> {
+    match format {
+        Format::Auto => unreachable!("format is resolved before parsing"),
+        Format::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+        Format::Ndjson => {
+            let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+            text.lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(n, line)| {
+                    serde_json::from_str(line).map_err(|e| format!("line {}: {e}", n + 1))
+                })
+                .collect()
+        }
+        Format::Binary => match decode_votes(bytes) {
+            Ok(votes) => Ok(votes.into_iter().map(ExportedVote::from).collect()),
+            Err(e) => {
+                let error = VoteError::from(e);
+                Err(format!("{}: {error}", error.code()))
+            }
+        },
+    }
+}
+
+/// Validates `votes` when `validators` and the current time are given, then
+/// tallies the rest chunk by chunk, as the node does for a full period.
+fn tally(
+    processor: &VoteProcessor,
+    votes: &[ExportedVote],
+    validators: Option<(&ValidatorSet, u64)>,
+    eligible_voters: u64,
+    timestamp: u64,
+) -> Result<Report, VoteError> {
+    let rejected = match validators {
+        Some((validators, now)) => {
+            let candidates: Vec<CandidateVote> =
+                votes.iter().map(ExportedVote::candidate).collect();
+            validation::validate_votes(&candidates, validators, now).rejected
+        }
+        None => Vec::new(),
+    };
+    let dropped: HashSet<usize> = rejected.iter().map(|r| r.index).collect();
+    let counted: Vec<&ExportedVote> = votes
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !dropped.contains(index))
+        .map(|(_, vote)| vote)
+        .collect();
+
+    let data: Vec<VoteData> = counted.iter().map(|vote| vote.vote_data()).collect();
+    let mut accumulator = processor.accumulator();
+    for chunk in data.chunks(processor.chunk_size().max(1)) {
+        accumulator.add_chunk(chunk)?;
+    }
+
+    let merkle_votes: Option<Vec<MerkleVote>> =
+        counted.iter().map(|vote| vote.merkle_vote()).collect();
+    let merkle_root = match merkle_votes {
+        Some(merkle_votes) if !merkle_votes.is_empty() => {
+            Some(merkle::votes_merkle_root(&merkle_votes)?)
+        }
+        _ => None,
+    };
+
+    Ok(Report {
+        tally: accumulator.tally(eligible_voters, timestamp),
+        merkle_root,
+        voters: accumulator.snapshot().details,
+        rejected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vote_processor::RejectionReason;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn encode(balance: i64, approved: i32, voter: &str) -> Vec<u8> {
+        let mut bytes = balance.to_le_bytes().to_vec();
+        bytes.extend(approved.to_le_bytes());
+        bytes.extend((voter.len() as i32).to_le_bytes());
+        bytes.extend(voter.as_bytes());
+        bytes
+    }
+
+    fn vote(voter: &str, balance: &str, approved: i32) -> ExportedVote {
+        ExportedVote {
+            balance: balance.to_string(),
+            approved,
+            voter: voter.to_string(),
+            timestamp: Some(NOW),
+            vote_id: Some(format!("id-{voter}")),
+            signature: "sig".to_string(),
+            chain_vote_data: Some(ChainVoteData {
+                target_chain_id: "chain-a".to_string(),
+                fork_height: Some(10.into()),
+                amount: Some("1".into()),
+            }),
+        }
+    }
+
+    #[test]
+    fn detects_formats() {
+        let path = |p: &'static str| Some(Path::new(p));
+        assert_eq!(detect_format(path("votes.json"), b"{"), Format::Json);
+        assert_eq!(detect_format(path("votes.JSONL"), b"["), Format::Ndjson);
+        assert_eq!(detect_format(path("votes.bin"), b"["), Format::Binary);
+        assert_eq!(detect_format(None, b" \n[{}]"), Format::Json);
+        assert_eq!(detect_format(path("votes"), b"{}\n{}"), Format::Ndjson);
+        assert_eq!(detect_format(None, &encode(5, 1, "a")), Format::Binary);
+    }
+
+    #[test]
+    fn parses_every_format_alike() {
+        let json = br#"[{"balance":"5","approved":1,"voter":"a"},
+                        {"balance":"7","approved":0,"voter":"b"}]"#;
+        let ndjson = b"{\"balance\":\"5\",\"approved\":1,\"voter\":\"a\"}\n\n\
+                       {\"balance\":\"7\",\"approved\":0,\"voter\":\"b\"}\n";
+        let mut binary = encode(5, 1, "a");
+        binary.extend(encode(7, 0, "b"));
+
+        let expected = parse_votes(json, Format::Json).unwrap();
+        assert_eq!(expected.len(), 2);
+        assert_eq!(parse_votes(ndjson, Format::Ndjson).unwrap(), expected);
+        assert_eq!(parse_votes(&binary, Format::Binary).unwrap(), expected);
+    }
+
+    #[test]
+    fn reports_where_input_is_malformed() {
+        let ndjson = b"{\"balance\":\"5\",\"approved\":1,\"voter\":\"a\"}\n{\"balance\":5}\n";
+        let error = parse_votes(ndjson, Format::Ndjson).unwrap_err();
+        assert!(error.starts_with("line 2:"), "{error}");
+
+        let error = parse_votes(&encode(5, 1, "abc")[..18], Format::Binary).unwrap_err();
+        assert!(error.starts_with("DECODE_ERROR:"), "{error}");
+    }
+
+    #[test]
+    fn matches_the_processor_tally() {
+        let votes = vec![vote("a", "100", 1), vote("b", "400", 0), vote("a", "44", 1)];
+        let mut processor = VoteProcessor::new();
+        processor.set_tally_mode(TallyMode::Quadratic);
+        processor.set_chunk_size(2);
+
+        processor.set_detailed(true);
+
+        let report = tally(&processor, &votes, None, 10, 99).unwrap();
+        processor.set_chunk_size(votes.len());
+        let data: Vec<VoteData> = votes.iter().map(ExportedVote::vote_data).collect();
+        assert_eq!(report.tally, processor.tally_votes(&data, 10, 99).unwrap());
+        assert_eq!(
+            report.voters,
+            processor.process_chunk(&data).unwrap().details
+        );
+
+        let merkle_votes: Vec<MerkleVote> =
+            votes.iter().map(|v| v.merkle_vote().unwrap()).collect();
+        assert_eq!(
+            report.merkle_root,
+            Some(merkle::votes_merkle_root(&merkle_votes).unwrap())
+        );
+        assert!(report.rejected.is_empty());
+    }
+
+    #[test]
+    fn drops_rejected_votes_before_tallying() {
+        let mut unsigned = vote("b", "400", 1);
+        unsigned.signature.clear();
+        let votes = vec![vote("a", "100", 1), unsigned, vote("stranger", "900", 1)];
+        let validators = ValidatorSet::new(["a", "b"].map(|address| ValidatorStatus {
+            address: address.to_string(),
+            is_active: true,
+        }));
+
+        let report = tally(
+            &VoteProcessor::new(),
+            &votes,
+            Some((&validators, NOW)),
+            0,
+            0,
+        )
+        .unwrap();
+        assert_eq!(report.tally.approved, 100);
+        assert_eq!(report.tally.total_votes, 1);
+        let rejected: Vec<_> = report
+            .rejected
+            .iter()
+            .map(|r| (r.index, r.reason.clone()))
+            .collect();
+        assert_eq!(
+            rejected,
+            vec![
+                (1, RejectionReason::MissingSignature),
+                (2, RejectionReason::UnknownValidator),
+            ]
+        );
+        assert_eq!(
+            report.merkle_root,
+            Some(merkle::votes_merkle_root(&[votes[0].merkle_vote().unwrap()]).unwrap())
+        );
+    }
+
+    #[test]
+    fn binary_votes_have_no_merkle_root() {
+        let votes = parse_votes(&encode(5, 1, "a"), Format::Binary).unwrap();
+        let report = tally(&VoteProcessor::new(), &votes, None, 0, 0).unwrap();
+        assert_eq!(report.tally.approved, 5);
+        assert_eq!(report.merkle_root, None);
+    }
+
+    #[test]
+    fn surfaces_tally_errors() {
+        let votes = vec![vote("a", "1", 1), vote("a", "2", 1)];
+        let mut processor = VoteProcessor::new();
+        processor.set_duplicate_policy(DuplicatePolicy::Reject);
+        let error = CliError::from(tally(&processor, &votes, None, 0, 0).unwrap_err());
+        assert!(error.to_string().starts_with("DUPLICATE_VOTE: "), "{error}");
+    }
+}
@@ -0,0 +1,201 @@
+//! `BlockHeader.consensusData` scores in fixed point.
+//!
+//! `HybridDirectConsensus` mixes `CONSENSUS.POW_WEIGHT` and
+//! `VOTING_CONSTANTS.VOTING_WEIGHT` with floats, so two nodes can rank
+//! competing blocks differently by a rounding error. Here every score is a
+//! [`Score`], an integer count of 10^-18 units, and each step rounds down,
+//! so the combined scores of two blocks compare the same way everywhere.
+
+use crate::error::VoteError;
+use crate::tally::VoteTally;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Mul;
+
+/// Number of [`Score`] units in 1.0.
+pub const SCORE_SCALE: u128 = 1_000_000_000_000_000_000;
+/// `CONSENSUS.POW_WEIGHT`.
+pub const POW_WEIGHT: Score = Score(600_000_000_000_000_000);
+/// `VOTING_CONSTANTS.VOTING_WEIGHT`.
+pub const VOTING_WEIGHT: Score = Score(400_000_000_000_000_000);
+
+/// A non-negative fixed-point number with 18 decimals. Serialized as the
+/// raw integer, a `BigInt` in JS.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Score(pub u128);
+
+impl Score {
+    pub const ZERO: Score = Score(0);
+    pub const ONE: Score = Score(SCORE_SCALE);
+
+    /// `numerator / denominator`, rounded down; zero when `denominator` is
+    /// zero. Operands too large to scale are both halved first, which only
+    /// happens far beyond the token supply.
+    pub fn ratio(mut numerator: u128, mut denominator: u128) -> Score {
+        if denominator == 0 {
+            return Score::ZERO;
+        }
+        while numerator > u128::MAX / SCORE_SCALE {
+            numerator >>= 1;
+            denominator >>= 1;
+        }
+        match denominator {
+            0 => Score(u128::MAX),
+            denominator => Score(numerator * SCORE_SCALE / denominator),
+        }
+    }
+
+    /// The nearest `f64`, for the `number` fields of `consensusData`. Not
+    /// for comparisons.
+    pub fn to_f64(self) -> f64 {
+        (self.0 / SCORE_SCALE) as f64 + (self.0 % SCORE_SCALE) as f64 / SCORE_SCALE as f64
+    }
+}
+
+/// Rounds down and saturates.
+impl Mul for Score {
+    type Output = Score;
+
+    fn mul(self, other: Score) -> Score {
+        let (whole, fraction) = (self.0 / SCORE_SCALE, self.0 % SCORE_SCALE);
+        whole
+            .checked_mul(other.0)
+            .zip(fraction.checked_mul(other.0))
+            .and_then(|(whole, fraction)| whole.checked_add(fraction / SCORE_SCALE))
+            .map_or(Score(u128::MAX), Score)
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:018}", self.0 / SCORE_SCALE, self.0 % SCORE_SCALE)
+    }
+}
+
+/// The scores of one block. `combined` is the value to compare between
+/// competing blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsensusScores {
+    /// Block difficulty over network difficulty, as `calculatePowScore`.
+    /// May exceed one.
+    pub pow_score: Score,
+    /// Approving share of the period's voting power.
+    pub voting_score: Score,
+    /// Unique voters over eligible voters, at most one.
+    pub participation_rate: Score,
+    /// `POW_WEIGHT * powScore + VOTING_WEIGHT * votingScore`.
+    pub combined: Score,
+}
+
+/// Scores a block with `difficulty` against the current period's `tally`.
+/// Fails with `INVALID_ARGUMENT` when `network_difficulty` is zero, as
+/// `calculatePowScore` does.
+pub fn consensus_scores(
+    difficulty: u64,
+    network_difficulty: u64,
+    tally: &VoteTally,
+    eligible_voters: u64,
+) -> Result<ConsensusScores, VoteError> {
+    if network_difficulty == 0 {
+        return Err(VoteError::InvalidArgument(
+            "Network difficulty is 0, cannot calculate PoW score".to_string(),
+        ));
+    }
+    let pow_score = Score::ratio(difficulty.into(), network_difficulty.into());
+    let (mut approved, mut rejected) = (tally.approved, tally.rejected);
+    if approved.checked_add(rejected).is_none() {
+        (approved, rejected) = (approved >> 1, rejected >> 1);
+    }
+    let voting_score = Score::ratio(approved, approved + rejected);
+    let participation_rate =
+        Score::ratio(tally.unique_voters as u128, eligible_voters.into()).min(Score::ONE);
+    let combined = Score(
+        (pow_score * POW_WEIGHT)
+            .0
+            .saturating_add((voting_score * VOTING_WEIGHT).0),
+    );
+    Ok(ConsensusScores {
+        pow_score,
+        voting_score,
+        participation_rate,
+        combined,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tally(approved: u128, rejected: u128, unique_voters: usize) -> VoteTally {
+        VoteTally {
+            approved,
+            rejected,
+            total_votes: unique_voters,
+            unique_voters,
+            participation_rate: 0.0,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn weights_sum_to_one() {
+        assert_eq!(POW_WEIGHT.0 + VOTING_WEIGHT.0, SCORE_SCALE);
+        assert_eq!(POW_WEIGHT.to_string(), "0.600000000000000000");
+        assert_eq!(VOTING_WEIGHT.to_f64(), 0.4);
+    }
+
+    #[test]
+    fn scores_work_and_votes() {
+        let scores = consensus_scores(150, 100, &tally(3, 1, 4), 8).unwrap();
+        assert_eq!(scores.pow_score.to_string(), "1.500000000000000000");
+        assert_eq!(scores.voting_score.to_string(), "0.750000000000000000");
+        assert_eq!(
+            scores.participation_rate.to_string(),
+            "0.500000000000000000"
+        );
+        // 0.6 * 1.5 + 0.4 * 0.75
+        assert_eq!(scores.combined.to_string(), "1.200000000000000000");
+    }
+
+    #[test]
+    fn rounds_down_and_compares_exactly() {
+        let a = consensus_scores(1, 3, &tally(2, 1, 1), 3).unwrap();
+        assert_eq!(a.pow_score, Score(333_333_333_333_333_333));
+        assert_eq!(a.voting_score, Score(666_666_666_666_666_666));
+        assert_eq!(
+            a.combined,
+            Score(199_999_999_999_999_999 + 266_666_666_666_666_666)
+        );
+
+        let b = consensus_scores(2, 3, &tally(1, 2, 1), 3).unwrap();
+        assert!(b.combined > a.combined);
+        assert_eq!(a.combined.cmp(&a.combined), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn handles_empty_tallies_and_limits() {
+        let scores = consensus_scores(0, 1, &tally(0, 0, 0), 0).unwrap();
+        assert_eq!(scores.combined, Score::ZERO);
+        assert_eq!(scores.participation_rate, Score::ZERO);
+
+        let scores = consensus_scores(u64::MAX, 1, &tally(u128::MAX, u128::MAX, 9), 3).unwrap();
+        assert_eq!(scores.participation_rate, Score::ONE);
+        assert!(Score::ONE.0 / 2 - scores.voting_score.0 < 1_000);
+
+        let err = consensus_scores(1, 0, &tally(0, 0, 0), 0).unwrap_err();
+        assert_eq!(err.code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn serializes_scores_as_integers() {
+        let scores = consensus_scores(1, 2, &tally(1, 0, 1), 1).unwrap();
+        assert_eq!(
+            serde_json::to_string(&scores).unwrap(),
+            r#"{"powScore":500000000000000000,"votingScore":1000000000000000000,"participationRate":1000000000000000000,"combined":700000000000000000}"#
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Chain selection from a finished tally, mirroring
+//! `DirectVotingUtil.processVotingResults` without floating-point division.
+
+use crate::error::VoteError;
+use crate::tally::VoteTally;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// An approval threshold expressed as an exact fraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Threshold {
+    numerator: u128,
+    denominator: u128,
+}
+
+/// `MINING.NODE_SELECTION_THRESHOLD` (0.67).
+pub const NODE_SELECTION_THRESHOLD: Threshold = Threshold {
+    numerator: 67,
+    denominator: 100,
+};
+
+impl Threshold {
+    /// A threshold of `numerator / denominator`, which must lie in `[0, 1]`.
+    pub fn new(numerator: u128, denominator: u128) -> Result<Self, VoteError> {
+        if denominator == 0 || numerator > denominator {
+            return Err(VoteError::InvalidThreshold {
+                numerator,
+                denominator,
+            });
+        }
+        Ok(Threshold {
+            numerator,
+            denominator,
+        })
+    }
+
+    pub fn numerator(&self) -> u128 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> u128 {
+        self.denominator
+    }
+
+    /// Whether `approved / (approved + rejected) >= self`. Rearranged as
+    /// `approved * (denominator - numerator) >= rejected * numerator` and
+    /// compared in 256-bit arithmetic, so it is exact for any inputs.
+    pub fn is_met_by(&self, approved: u128, rejected: u128) -> bool {
+        mul_wide(approved, self.denominator - self.numerator) >= mul_wide(rejected, self.numerator)
+    }
+}
+
+/// Full 256-bit product of two `u128`s as `(high, low)`, which orders the
+/// same way as the product itself.
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_hi = a_hi * b_hi;
+
+    let cross = (lo_lo >> 64) + (hi_lo & MASK) + (lo_hi & MASK);
+    let low = (cross << 64) | (lo_lo & MASK);
+    let high = hi_hi + (hi_lo >> 64) + (lo_hi >> 64) + (cross >> 64);
+    (high, low)
+}
+
+/// Outcome of comparing a tally against the selection threshold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainDecision {
+    pub selected_chain: String,
+    pub new_chain_selected: bool,
+    /// `approved / (approved + rejected)` for metrics and audit logs. The
+    /// decision itself never uses this value.
+    pub approval_ratio: f64,
+    /// Vote power behind each chain, as decimal strings.
+    pub vote_powers: BTreeMap<String, String>,
+}
+
+/// The TypeScript `ForkDecision` record (`models/vote.model.ts`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkDecision {
+    pub selected_chain: String,
+    pub vote_powers: BTreeMap<String, String>,
+    pub decided_at: u64,
+    pub fork_height: u64,
+}
+
+impl ChainDecision {
+    pub fn fork_decision(&self, fork_height: u64, decided_at: u64) -> ForkDecision {
+        ForkDecision {
+            selected_chain: self.selected_chain.clone(),
+            vote_powers: self.vote_powers.clone(),
+            decided_at,
+            fork_height,
+        }
+    }
+}
+
+/// Selects `new_chain_id` when the approved share of the tally meets
+/// `threshold`, and `old_chain_id` otherwise (including when nobody voted).
+/// Fails with `INVALID_ARGUMENT` when the two IDs are equal, since
+/// `vote_powers` could not hold both chains' power.
+pub fn decide_chain(
+    tally: &VoteTally,
+    old_chain_id: &str,
+    new_chain_id: &str,
+    threshold: Threshold,
+) -> Result<ChainDecision, VoteError> {
+    if old_chain_id == new_chain_id {
+        return Err(VoteError::InvalidArgument(format!(
+            "Old and new chain IDs are both {old_chain_id}"
+        )));
+    }
+    let (approved, rejected) = (tally.approved, tally.rejected);
+    let no_votes = approved == 0 && rejected == 0;
+    let new_chain_selected = !no_votes && threshold.is_met_by(approved, rejected);
+
+    let approval_ratio = if approved == 0 {
+        0.0
+    } else {
+        approved as f64 / (approved as f64 + rejected as f64)
+    };
+
+    let mut vote_powers = BTreeMap::new();
+    vote_powers.insert(old_chain_id.to_string(), rejected.to_string());
+    vote_powers.insert(new_chain_id.to_string(), approved.to_string());
+
+    Ok(ChainDecision {
+        selected_chain: if new_chain_selected {
+            new_chain_id
+        } else {
+            old_chain_id
+        }
+        .to_string(),
+        new_chain_selected,
+        approval_ratio,
+        vote_powers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tally(approved: u128, rejected: u128) -> VoteTally {
+        VoteTally {
+            approved,
+            rejected,
+            total_votes: 0,
+            unique_voters: 0,
+            participation_rate: 0.0,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn threshold_is_inclusive_and_exact() {
+        let decide =
+            |a, r| decide_chain(&tally(a, r), "old", "new", NODE_SELECTION_THRESHOLD).unwrap();
+
+        let decision = decide(67, 33);
+        assert!(decision.new_chain_selected);
+        assert_eq!(decision.selected_chain, "new");
+        assert_eq!(decision.approval_ratio, 0.67);
+
+        assert_eq!(decide(66, 34).selected_chain, "old");
+        // 2/3 is below 0.67 even though it rounds to it.
+        assert_eq!(decide(2, 1).selected_chain, "old");
+        // A ratio that f64 division would round up to 0.67.
+        let whole = 10u128.pow(30);
+        let just_below = whole * 67 / 100 - 1;
+        assert_eq!(decide(just_below, whole - just_below).selected_chain, "old");
+    }
+
+    #[test]
+    fn no_votes_keeps_old_chain() {
+        let decision = decide_chain(&tally(0, 0), "old", "new", NODE_SELECTION_THRESHOLD).unwrap();
+        assert_eq!(decision.selected_chain, "old");
+        assert_eq!(decision.approval_ratio, 0.0);
+    }
+
+    #[test]
+    fn rejects_identical_chain_ids() {
+        let err = decide_chain(&tally(3, 1), "same", "same", NODE_SELECTION_THRESHOLD).unwrap_err();
+        assert_eq!(err.code(), "INVALID_ARGUMENT");
+    }
+
+    #[test]
+    fn handles_totals_beyond_u128_range() {
+        let decision = decide_chain(
+            &tally(u128::MAX, u128::MAX / 3),
+            "old",
+            "new",
+            NODE_SELECTION_THRESHOLD,
+        )
+        .unwrap();
+        assert!(decision.new_chain_selected);
+
+        let decision = decide_chain(
+            &tally(u128::MAX / 2, u128::MAX),
+            "old",
+            "new",
+            NODE_SELECTION_THRESHOLD,
+        )
+        .unwrap();
+        assert!(!decision.new_chain_selected);
+    }
+
+    #[test]
+    fn builds_fork_decision() {
+        let decision =
+            decide_chain(&tally(80, 20), "old", "new", NODE_SELECTION_THRESHOLD).unwrap();
+        let fork = decision.fork_decision(1_200, 1_700_000_000_000);
+        assert_eq!(fork.selected_chain, "new");
+        assert_eq!(fork.vote_powers["new"], "80");
+        assert_eq!(fork.vote_powers["old"], "20");
+        assert_eq!(
+            (fork.fork_height, fork.decided_at),
+            (1_200, 1_700_000_000_000)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_thresholds() {
+        assert!(Threshold::new(1, 0).is_err());
+        assert!(Threshold::new(3, 2).is_err());
+        assert_eq!(Threshold::new(67, 100).unwrap(), NODE_SELECTION_THRESHOLD);
+    }
+
+    #[test]
+    fn mul_wide_matches_known_products() {
+        assert_eq!(mul_wide(0, u128::MAX), (0, 0));
+        assert_eq!(mul_wide(u128::MAX, 1), (0, u128::MAX));
+        assert_eq!(mul_wide(1 << 64, 1 << 64), (1, 0));
+        // (2^128 - 1)^2 = 2^256 - 2^129 + 1
+        assert_eq!(mul_wide(u128::MAX, u128::MAX), (u128::MAX - 1, 1));
+    }
+}
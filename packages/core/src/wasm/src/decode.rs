@@ -0,0 +1,219 @@
+//! Decoder for the binary vote layout written by `serializeVotes` in
+//! `vote-processor.ts`.
+//!
+//! Each vote is packed as:
+//!
+//! ```text
+//! [i64 balance LE][i32 approved LE][i32 voterLen LE][voterLen bytes of UTF-8 voter]
+//! ```
+//!
+//! Votes are concatenated with no padding and no leading count.
+
+use crate::processor::VoteData;
+use std::fmt;
+
+/// Size of the fixed part of a vote record (balance + approved + voter length).
+pub const VOTE_HEADER_SIZE: usize = 8 + 4 + 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeErrorKind {
+    /// The buffer ended before `needed` bytes of a field could be read.
+    Truncated { needed: usize, available: usize },
+    /// The encoded balance is below zero.
+    NegativeBalance(i64),
+    /// The encoded voter length is below zero.
+    NegativeVoterLength(i32),
+    /// The voter bytes are not valid UTF-8.
+    InvalidUtf8,
+}
+
+/// A decoding failure, located by the index of the vote being read and the
+/// byte offset of the field that could not be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    pub index: usize,
+    pub offset: usize,
+    pub kind: DecodeErrorKind,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "vote {} at byte offset {}: ", self.index, self.offset)?;
+        match &self.kind {
+            DecodeErrorKind::Truncated { needed, available } => write!(
+                f,
+                "truncated input, needed {needed} bytes but only {available} remain"
+            ),
+            DecodeErrorKind::NegativeBalance(balance) => write!(f, "negative balance {balance}"),
+            DecodeErrorKind::NegativeVoterLength(len) => write!(f, "negative voter length {len}"),
+            DecodeErrorKind::InvalidUtf8 => write!(f, "voter is not valid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, offset: usize, kind: DecodeErrorKind) -> DecodeError {
+        DecodeError {
+            index: self.index,
+            offset,
+            kind,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let available = self.bytes.len() - self.offset;
+        if len > available {
+            return Err(self.error(
+                self.offset,
+                DecodeErrorKind::Truncated {
+                    needed: len,
+                    available,
+                },
+            ));
+        }
+        let slice = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(slice)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut buf = [0u8; N];
+        buf.copy_from_slice(self.take(N)?);
+        Ok(buf)
+    }
+}
+
+/// Decodes every vote in `bytes`. Trailing bytes that do not form a complete
+/// vote are reported as [`DecodeErrorKind::Truncated`].
+pub fn decode_votes(bytes: &[u8]) -> Result<Vec<VoteData>, DecodeError> {
+    let mut reader = Reader {
+        bytes,
+        offset: 0,
+        index: 0,
+    };
+    let mut votes = Vec::with_capacity(bytes.len() / VOTE_HEADER_SIZE);
+
+    while reader.offset < bytes.len() {
+        let balance_offset = reader.offset;
+        let balance = i64::from_le_bytes(reader.read_array()?);
+        if balance < 0 {
+            return Err(reader.error(balance_offset, DecodeErrorKind::NegativeBalance(balance)));
+        }
+
+        let approved = i32::from_le_bytes(reader.read_array()?);
+
+        let len_offset = reader.offset;
+        let voter_len = i32::from_le_bytes(reader.read_array()?);
+        let voter_len = usize::try_from(voter_len).map_err(|_| {
+            reader.error(len_offset, DecodeErrorKind::NegativeVoterLength(voter_len))
+        })?;
+
+        let voter_offset = reader.offset;
+        let voter = std::str::from_utf8(reader.take(voter_len)?).map_err(|e| {
+            reader.error(voter_offset + e.valid_up_to(), DecodeErrorKind::InvalidUtf8)
+        })?;
+
+        votes.push(VoteData {
+            balance: balance.to_string(),
+            approved,
+            voter: voter.to_string(),
+            timestamp: None,
+        });
+        reader.index += 1;
+    }
+
+    Ok(votes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(balance: i64, approved: i32, voter: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&balance.to_le_bytes());
+        out.extend_from_slice(&approved.to_le_bytes());
+        out.extend_from_slice(&(voter.len() as i32).to_le_bytes());
+        out.extend_from_slice(voter);
+        out
+    }
+
+    #[test]
+    fn decodes_serialize_votes_layout() {
+        let mut bytes = encode(1_000, 1, b"alice");
+        bytes.extend(encode(i64::MAX, 0, "b\u{00f6}b".as_bytes()));
+
+        let votes = decode_votes(&bytes).unwrap();
+        assert_eq!(votes.len(), 2);
+        assert_eq!(votes[0].balance, "1000");
+        assert_eq!(votes[0].approved, 1);
+        assert_eq!(votes[0].voter, "alice");
+        assert_eq!(votes[1].balance, i64::MAX.to_string());
+        assert_eq!(votes[1].voter, "b\u{00f6}b");
+    }
+
+    #[test]
+    fn empty_input_has_no_votes() {
+        assert!(decode_votes(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reports_truncated_voter() {
+        let mut bytes = encode(5, 1, b"a");
+        let mut second = encode(5, 1, b"carol");
+        second.truncate(second.len() - 2);
+        bytes.extend(second);
+
+        let err = decode_votes(&bytes).unwrap_err();
+        assert_eq!(err.index, 1);
+        assert_eq!(err.offset, 17 + VOTE_HEADER_SIZE);
+        assert_eq!(
+            err.kind,
+            DecodeErrorKind::Truncated {
+                needed: 5,
+                available: 3
+            }
+        );
+    }
+
+    #[test]
+    fn reports_truncated_header() {
+        let err = decode_votes(&[0u8; 10]).unwrap_err();
+        assert_eq!(err.offset, 8);
+        assert!(matches!(
+            err.kind,
+            DecodeErrorKind::Truncated {
+                needed: 4,
+                available: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn rejects_negative_fields_and_bad_utf8() {
+        let err = decode_votes(&encode(-1, 1, b"a")).unwrap_err();
+        assert_eq!(err.kind, DecodeErrorKind::NegativeBalance(-1));
+
+        let mut bytes = encode(1, 1, b"");
+        bytes[12..16].copy_from_slice(&(-3i32).to_le_bytes());
+        let err = decode_votes(&bytes).unwrap_err();
+        assert_eq!(
+            (err.offset, err.kind),
+            (12, DecodeErrorKind::NegativeVoterLength(-3))
+        );
+
+        let err = decode_votes(&encode(1, 1, &[b'o', b'k', 0xff])).unwrap_err();
+        assert_eq!(
+            (err.offset, err.kind),
+            (VOTE_HEADER_SIZE + 2, DecodeErrorKind::InvalidUtf8)
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Per-voter breakdown of a tally, for `getVotesByAddress`-style views.
+//!
+//! Only built when the processor's detailed mode is on (see
+//! [`VoteProcessor::set_detailed`]). Entries are sorted by voter, like
+//! [`ChunkResult::voters`].
+//!
+//! [`VoteProcessor::set_detailed`]: crate::processor::VoteProcessor::set_detailed
+//! [`ChunkResult::voters`]: crate::processor::ChunkResult::voters
+
+use crate::processor::{VoteData, VoteWeight};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Direction of a voter's counted votes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VoterChoice {
+    Approve,
+    Reject,
+    /// Counted votes in both directions, which only happens under
+    /// [`DuplicatePolicy::Sum`].
+    ///
+    /// [`DuplicatePolicy::Sum`]: crate::duplicates::DuplicatePolicy::Sum
+    Split,
+}
+
+impl VoterChoice {
+    /// The choice of a single vote: `approved > 0` approves.
+    pub fn of(approved: i32) -> Self {
+        if approved > 0 {
+            VoterChoice::Approve
+        } else {
+            VoterChoice::Reject
+        }
+    }
+
+    /// The serialized name: `"approve"`, `"reject"` or `"split"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VoterChoice::Approve => "approve",
+            VoterChoice::Reject => "reject",
+            VoterChoice::Split => "split",
+        }
+    }
+
+    /// The choice of two sets of counted votes taken together.
+    pub fn combine(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            VoterChoice::Split
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoterDetail {
+    pub voter: String,
+    /// Votes cast, whether the duplicate policy counted them or not.
+    pub vote_count: usize,
+    /// Sum of the balances of the counted votes. Serialized to JS as a `BigInt`.
+    pub balance: u128,
+    /// Weight the counted votes added to the tally (see [`TallyMode`]).
+    /// Serialized to JS as a `BigInt`.
+    ///
+    /// [`TallyMode`]: crate::power::TallyMode
+    pub power: u128,
+    pub choice: VoterChoice,
+}
+
+/// Breaks a chunk down by voter. `weights` and `counted` are per vote, as
+/// computed by [`VoteProcessor::process_chunk`].
+///
+/// Balances are at most `MAX_AMOUNT` (below 2^53), so per-voter sums cannot
+/// overflow `u128`; powers are bounded by the checked chunk totals.
+///
+/// [`VoteProcessor::process_chunk`]: crate::processor::VoteProcessor::process_chunk
+pub(crate) fn voter_details(
+    votes: &[VoteData],
+    weights: &[VoteWeight],
+    counted: &[bool],
+) -> Vec<VoterDetail> {
+    let mut details: BTreeMap<&str, (usize, u128, u128, Option<VoterChoice>)> = BTreeMap::new();
+    for ((vote, weight), &counted) in votes.iter().zip(weights).zip(counted) {
+        let (vote_count, balance, power, choice) = details.entry(&vote.voter).or_default();
+        *vote_count += 1;
+        if counted {
+            let vote_choice = VoterChoice::of(vote.approved);
+            *balance += weight.balance;
+            *power += weight.approved + weight.rejected;
+            *choice = Some(choice.map_or(vote_choice, |c| c.combine(vote_choice)));
+        }
+    }
+
+    details
+        .into_iter()
+        .map(
+            |(voter, (vote_count, balance, power, choice))| VoterDetail {
+                voter: voter.to_string(),
+                vote_count,
+                balance,
+                power,
+                // Every policy counts at least one vote per voter.
+                choice: choice.expect("every voter has a counted vote"),
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combines_choices() {
+        use VoterChoice::*;
+        assert_eq!(VoterChoice::of(1), Approve);
+        assert_eq!(VoterChoice::of(0), Reject);
+        assert_eq!(VoterChoice::of(-1), Reject);
+        assert_eq!(Approve.combine(Approve), Approve);
+        assert_eq!(Approve.combine(Reject), Split);
+        assert_eq!(Split.combine(Reject), Split);
+        for choice in [Approve, Reject, Split] {
+            assert_eq!(
+                serde_json::to_string(&choice).unwrap(),
+                format!("\"{}\"", choice.as_str())
+            );
+        }
+    }
+
+    #[test]
+    fn serializes_camel_case() {
+        let detail = VoterDetail {
+            voter: "a".to_string(),
+            vote_count: 2,
+            balance: 10,
+            power: 3,
+            choice: VoterChoice::Split,
+        };
+        assert_eq!(
+            serde_json::to_string(&detail).unwrap(),
+            r#"{"voter":"a","voteCount":2,"balance":10,"power":3,"choice":"split"}"#
+        );
+    }
+}
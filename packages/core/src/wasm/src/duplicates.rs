@@ -0,0 +1,221 @@
+//! Detection and resolution of voters that appear more than once in a chunk.
+
+use crate::error::VoteError;
+use crate::processor::VoteData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// What to do when a voter casts more than one vote in a chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Fail the whole chunk.
+    Reject,
+    /// Count only the voter's first vote in chunk order, as
+    /// `DirectVotingUtil` ignores votes from a voter that has already voted.
+    #[default]
+    FirstWins,
+    /// Count only the voter's vote with the latest `timestamp`. A vote
+    /// without a timestamp is older than any vote with one, and ties fall
+    /// back to chunk order, so the last of several untimed votes counts.
+    LastWins,
+    /// Count every vote, so a voter's power counts once per vote. This was
+    /// the behaviour before duplicates were detected; it must be chosen
+    /// explicitly.
+    Sum,
+}
+
+/// Parses the names used by the JS bindings: `"reject"`, `"first-wins"`,
+/// `"last-wins"` or `"sum"`.
+impl FromStr for DuplicatePolicy {
+    type Err = VoteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(DuplicatePolicy::Reject),
+            "first-wins" => Ok(DuplicatePolicy::FirstWins),
+            "last-wins" => Ok(DuplicatePolicy::LastWins),
+            "sum" => Ok(DuplicatePolicy::Sum),
+            other => Err(VoteError::InvalidArgument(format!(
+                "Unknown duplicate policy: {other}"
+            ))),
+        }
+    }
+}
+
+/// Anything cast by a voter that duplicate detection can group.
+pub trait Ballot {
+    fn voter(&self) -> &str;
+    fn timestamp(&self) -> Option<u64>;
+}
+
+impl Ballot for VoteData {
+    fn voter(&self) -> &str {
+        &self.voter
+    }
+
+    fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+}
+
+/// A voter that cast more than one vote, with the chunk indices of all of
+/// their votes in ascending order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateVoter {
+    pub voter: String,
+    pub indices: Vec<usize>,
+}
+
+/// Finds every voter with more than one vote, ordered by the index of their
+/// first vote.
+pub fn find_duplicates<B: Ballot>(votes: &[B]) -> Vec<DuplicateVoter> {
+    let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+    let mut order = Vec::new();
+    for (index, vote) in votes.iter().enumerate() {
+        let indices = groups.entry(vote.voter()).or_default();
+        if indices.is_empty() {
+            order.push(vote.voter());
+        }
+        indices.push(index);
+    }
+
+    order
+        .into_iter()
+        .filter_map(|voter| {
+            let indices = groups.remove(voter)?;
+            (indices.len() > 1).then(|| DuplicateVoter {
+                voter: voter.to_string(),
+                indices,
+            })
+        })
+        .collect()
+}
+
+/// Returns a per-vote mask of which votes should be counted under `policy`.
+pub fn counted_mask<B: Ballot>(
+    votes: &[B],
+    duplicates: &[DuplicateVoter],
+    policy: DuplicatePolicy,
+) -> Result<Vec<bool>, VoteError> {
+    let mut mask = vec![true; votes.len()];
+    for duplicate in duplicates {
+        let keep = match policy {
+            DuplicatePolicy::Sum => continue,
+            DuplicatePolicy::Reject => {
+                return Err(VoteError::Duplicate {
+                    voter: duplicate.voter.clone(),
+                    indices: duplicate.indices.clone(),
+                })
+            }
+            DuplicatePolicy::FirstWins => duplicate.indices[0],
+            DuplicatePolicy::LastWins => *duplicate
+                .indices
+                .iter()
+                .max_by_key(|&&index| (votes[index].timestamp().unwrap_or(0), index))
+                .expect("duplicate groups are never empty"),
+        };
+        for &index in &duplicate.indices {
+            mask[index] = index == keep;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote(voter: &str, timestamp: Option<u64>) -> VoteData {
+        VoteData {
+            balance: "1".to_string(),
+            approved: 1,
+            voter: voter.to_string(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn parses_policy_names() {
+        assert_eq!("reject".parse(), Ok(DuplicatePolicy::Reject));
+        assert_eq!("first-wins".parse(), Ok(DuplicatePolicy::FirstWins));
+        assert_eq!("last-wins".parse(), Ok(DuplicatePolicy::LastWins));
+        assert_eq!("sum".parse(), Ok(DuplicatePolicy::Sum));
+        assert!("first_wins".parse::<DuplicatePolicy>().is_err());
+    }
+
+    #[test]
+    fn groups_duplicates_by_first_occurrence() {
+        let votes = vec![
+            vote("b", None),
+            vote("a", None),
+            vote("c", None),
+            vote("a", None),
+            vote("b", None),
+            vote("b", None),
+        ];
+        let duplicates = find_duplicates(&votes);
+        assert_eq!(
+            duplicates,
+            vec![
+                DuplicateVoter {
+                    voter: "b".into(),
+                    indices: vec![0, 4, 5]
+                },
+                DuplicateVoter {
+                    voter: "a".into(),
+                    indices: vec![1, 3]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn masks_follow_policy() {
+        let votes = vec![
+            vote("a", Some(30)),
+            vote("a", Some(50)),
+            vote("b", None),
+            vote("a", Some(10)),
+        ];
+        let duplicates = find_duplicates(&votes);
+
+        let mask = |policy| counted_mask(&votes, &duplicates, policy);
+        assert_eq!(mask(DuplicatePolicy::Sum).unwrap(), [true; 4]);
+        assert_eq!(
+            mask(DuplicatePolicy::FirstWins).unwrap(),
+            [true, false, true, false]
+        );
+        assert_eq!(
+            mask(DuplicatePolicy::LastWins).unwrap(),
+            [false, true, true, false]
+        );
+        assert_eq!(
+            mask(DuplicatePolicy::Reject).unwrap_err(),
+            VoteError::Duplicate {
+                voter: "a".to_string(),
+                indices: vec![0, 1, 3],
+            }
+        );
+    }
+
+    #[test]
+    fn last_wins_without_timestamps_uses_chunk_order() {
+        let votes = vec![vote("a", None), vote("a", None)];
+        let duplicates = find_duplicates(&votes);
+        let mask = counted_mask(&votes, &duplicates, DuplicatePolicy::LastWins).unwrap();
+        assert_eq!(mask, [false, true]);
+    }
+
+    #[test]
+    fn last_wins_puts_untimed_votes_before_timed_ones() {
+        let votes = vec![vote("a", Some(5)), vote("a", None), vote("a", Some(3))];
+        let duplicates = find_duplicates(&votes);
+        let mask = counted_mask(&votes, &duplicates, DuplicatePolicy::LastWins).unwrap();
+        assert_eq!(mask, [true, false, false]);
+
+        let votes = vec![vote("a", Some(1)), vote("a", None)];
+        let mask = counted_mask(&votes, &find_duplicates(&votes), DuplicatePolicy::LastWins);
+        assert_eq!(mask.unwrap(), [true, false]);
+    }
+}
@@ -0,0 +1,295 @@
+//! Errors returned by the vote processor.
+//!
+//! Every variant has a stable [`VoteError::code`] and, where the failure is
+//! tied to one vote, the vote's index and voter, so callers can branch on
+//! the failure without matching message text. The wasm layer turns these
+//! into a JS `VoteProcessorError` carrying the same `code`, `index` and
+//! `voter`.
+
+use crate::amount::AmountError;
+use crate::decode::{DecodeError, DecodeErrorKind};
+use crate::period::PeriodStatus;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoteError {
+    /// A balance or amount is not a canonical [`Amount`] string.
+    ///
+    /// [`Amount`]: crate::amount::Amount
+    InvalidAmount {
+        index: usize,
+        voter: String,
+        value: String,
+        reason: AmountError,
+    },
+    /// A running total exceeded `u128`. `tally` names the total
+    /// (`"approved"`, `"rejected"` or a chain ID); `index` and `voter` are
+    /// the vote that pushed it over, when there is one.
+    Overflow {
+        tally: String,
+        index: Option<usize>,
+        voter: Option<String>,
+    },
+    /// A voter voted more than once under [`DuplicatePolicy::Reject`].
+    ///
+    /// [`DuplicatePolicy::Reject`]: crate::duplicates::DuplicatePolicy::Reject
+    Duplicate { voter: String, indices: Vec<usize> },
+    /// More votes than the processor's chunk size.
+    ChunkTooLarge { len: usize, max: usize },
+    /// The binary vote layout could not be decoded.
+    Decode(DecodeError),
+    /// A chain vote was cast for a different fork.
+    ForkHeightMismatch {
+        index: usize,
+        voter: String,
+        fork_height: u64,
+        expected: u64,
+    },
+    /// Accumulators with different tally modes, duplicate policies or
+    /// weightings.
+    SettingsMismatch,
+    /// A threshold outside `[0, 1]` or with a zero denominator.
+    InvalidThreshold { numerator: u128, denominator: u128 },
+    /// A Merkle tree needs at least one leaf.
+    EmptyMerkleInput,
+    /// Merkle leaves must be non-empty strings.
+    EmptyMerkleLeaf { index: usize },
+    /// A proof was requested for a leaf the tree does not have.
+    LeafIndexOutOfRange { index: usize, leaves: usize },
+    /// Votes were added to a voting period that is no longer active.
+    InactivePeriod {
+        period_id: u64,
+        status: PeriodStatus,
+    },
+    /// A period event that the period's status does not allow, such as a
+    /// block for a period that has already completed.
+    InvalidTransition {
+        period_id: u64,
+        status: PeriodStatus,
+        event: &'static str,
+    },
+    /// A block height or time earlier than one the period has already seen.
+    /// `clock` is `"height"` or `"time"`.
+    StaleEvent {
+        period_id: u64,
+        clock: &'static str,
+        value: u64,
+        last: u64,
+    },
+    /// A chain-selection period was requested for a fork more than
+    /// `MAX_FORK_DEPTH` blocks below the current height.
+    ForkTooDeep {
+        fork_height: u64,
+        current_height: u64,
+        depth: u64,
+        max: u64,
+    },
+    /// An argument from the caller could not be used, such as an unknown
+    /// tally mode or a value that does not deserialize.
+    InvalidArgument(String),
+}
+
+impl VoteError {
+    /// Stable, machine-readable error code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            VoteError::InvalidAmount {
+                reason: AmountError::ExceedsMaxSupply,
+                ..
+            } => "AMOUNT_OUT_OF_RANGE",
+            VoteError::InvalidAmount { .. } => "PARSE_ERROR",
+            VoteError::Overflow { .. } => "OVERFLOW",
+            VoteError::Duplicate { .. } => "DUPLICATE_VOTE",
+            VoteError::ChunkTooLarge { .. } => "CHUNK_TOO_LARGE",
+            VoteError::Decode(DecodeError {
+                kind: DecodeErrorKind::NegativeVoterLength(_) | DecodeErrorKind::InvalidUtf8,
+                ..
+            }) => "INVALID_VOTER",
+            VoteError::Decode(_) => "DECODE_ERROR",
+            VoteError::ForkHeightMismatch { .. } => "FORK_HEIGHT_MISMATCH",
+            VoteError::SettingsMismatch => "SETTINGS_MISMATCH",
+            VoteError::InvalidThreshold { .. } => "INVALID_THRESHOLD",
+            VoteError::EmptyMerkleInput | VoteError::EmptyMerkleLeaf { .. } => {
+                "INVALID_MERKLE_INPUT"
+            }
+            VoteError::LeafIndexOutOfRange { .. } => "INVALID_LEAF_INDEX",
+            VoteError::InactivePeriod { .. } => "INACTIVE_PERIOD",
+            VoteError::InvalidTransition { .. } => "INVALID_TRANSITION",
+            VoteError::StaleEvent { .. } => "STALE_EVENT",
+            VoteError::ForkTooDeep { .. } => "FORK_DEPTH_EXCEEDED",
+            VoteError::InvalidArgument(_) => "INVALID_ARGUMENT",
+        }
+    }
+
+    /// Index of the failing vote or leaf. For duplicates this is the
+    /// voter's second vote, the first one that could not be counted.
+    pub fn index(&self) -> Option<usize> {
+        match self {
+            VoteError::InvalidAmount { index, .. }
+            | VoteError::ForkHeightMismatch { index, .. }
+            | VoteError::EmptyMerkleLeaf { index }
+            | VoteError::LeafIndexOutOfRange { index, .. } => Some(*index),
+            VoteError::Overflow { index, .. } => *index,
+            VoteError::Duplicate { indices, .. } => indices.get(1).copied(),
+            VoteError::Decode(error) => Some(error.index),
+            _ => None,
+        }
+    }
+
+    /// Voter of the failing vote.
+    pub fn voter(&self) -> Option<&str> {
+        match self {
+            VoteError::InvalidAmount { voter, .. }
+            | VoteError::Duplicate { voter, .. }
+            | VoteError::ForkHeightMismatch { voter, .. } => Some(voter),
+            VoteError::Overflow { voter, .. } => voter.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for VoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoteError::InvalidAmount {
+                index,
+                voter,
+                value,
+                reason,
+            } => write!(
+                f,
+                "Invalid amount {value:?} for voter {voter} at vote {index}: {reason}"
+            ),
+            VoteError::Overflow { tally, index, .. } => {
+                write!(f, "Tally overflow in {tally} total")?;
+                match index {
+                    Some(index) => write!(f, " at vote {index}"),
+                    None => Ok(()),
+                }
+            }
+            VoteError::Duplicate { voter, indices } => {
+                write!(
+                    f,
+                    "Duplicate votes from voter {voter} at indices {indices:?}"
+                )
+            }
+            VoteError::ChunkTooLarge { len, max } => {
+                write!(f, "Chunk size {len} exceeds maximum of {max}")
+            }
+            VoteError::Decode(error) => write!(f, "Invalid vote data: {error}"),
+            VoteError::ForkHeightMismatch {
+                voter,
+                fork_height,
+                expected,
+                ..
+            } => write!(
+                f,
+                "Vote from voter {voter} targets fork height {fork_height}, expected {expected}"
+            ),
+            VoteError::SettingsMismatch => {
+                write!(f, "Cannot merge accumulators with different settings")
+            }
+            VoteError::InvalidThreshold {
+                numerator,
+                denominator,
+            } => write!(
+                f,
+                "Invalid threshold {numerator}/{denominator}: must be a fraction between 0 and 1"
+            ),
+            VoteError::EmptyMerkleInput => {
+                write!(f, "Invalid input: data must be non-empty array")
+            }
+            VoteError::EmptyMerkleLeaf { index } => {
+                write!(f, "Invalid input: data item {index} is an empty string")
+            }
+            VoteError::LeafIndexOutOfRange { index, leaves } => write!(
+                f,
+                "Invalid leaf index: {index}. Valid range: 0-{}",
+                leaves.saturating_sub(1)
+            ),
+            VoteError::InactivePeriod { period_id, status } => {
+                write!(f, "Voting period {period_id} is {status}, not active")
+            }
+            VoteError::InvalidTransition {
+                period_id,
+                status,
+                event,
+            } => write!(
+                f,
+                "Invalid transition: {event} event for voting period {period_id}, which is {status}"
+            ),
+            VoteError::StaleEvent {
+                period_id,
+                clock,
+                value,
+                last,
+            } => write!(
+                f,
+                "Stale event for voting period {period_id}: {clock} {value} is before {last}"
+            ),
+            VoteError::ForkTooDeep { depth, max, .. } => write!(
+                f,
+                "Fork depth exceeds maximum allowed: current depth {depth} exceeds max allowed {max}"
+            ),
+            VoteError::InvalidArgument(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for VoteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VoteError::Decode(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for VoteError {
+    fn from(error: DecodeError) -> Self {
+        VoteError::Decode(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposes_code_index_and_voter() {
+        let error = VoteError::Duplicate {
+            voter: "a".to_string(),
+            indices: vec![2, 5, 9],
+        };
+        assert_eq!(error.code(), "DUPLICATE_VOTE");
+        assert_eq!(error.index(), Some(5));
+        assert_eq!(error.voter(), Some("a"));
+
+        let error = VoteError::ChunkTooLarge { len: 3, max: 2 };
+        assert_eq!(error.code(), "CHUNK_TOO_LARGE");
+        assert_eq!(error.index(), None);
+        assert_eq!(error.to_string(), "Chunk size 3 exceeds maximum of 2");
+    }
+
+    #[test]
+    fn classifies_decode_errors() {
+        let decode = |kind| {
+            VoteError::from(DecodeError {
+                index: 4,
+                offset: 80,
+                kind,
+            })
+        };
+
+        let error = decode(DecodeErrorKind::InvalidUtf8);
+        assert_eq!(error.code(), "INVALID_VOTER");
+        assert_eq!(error.index(), Some(4));
+
+        let error = decode(DecodeErrorKind::Truncated {
+            needed: 8,
+            available: 3,
+        });
+        assert_eq!(error.code(), "DECODE_ERROR");
+        assert!(error.to_string().contains("vote 4 at byte offset 80"));
+    }
+}
@@ -1,14 +1,55 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Vote processor for the H3Tag blockchain.
+//!
+//! The tally logic lives in [`processor`] and is plain Rust, so it can be
+//! unit-tested natively with `cargo test`. [`wasm`] is a thin
+//! `wasm-bindgen` layer that exposes it to JavaScript, and `node` (behind
+//! the `node` feature) exposes it as a Node-API addon.
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod accumulator;
+pub mod amount;
+pub mod consensus;
+pub mod decision;
+pub mod decode;
+pub mod details;
+pub mod duplicates;
+pub mod error;
+pub mod memory;
+pub mod merkle;
+pub mod multichain;
+#[cfg(feature = "node")]
+pub mod node;
+pub mod parallelism;
+pub mod period;
+pub mod power;
+pub mod processor;
+pub mod quota;
+pub mod signature;
+pub mod tally;
+#[cfg(all(target_arch = "wasm32", feature = "threads"))]
+pub mod thread_pool;
+pub mod validation;
+pub mod wasm;
+pub mod weighting;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use accumulator::VoteTallyAccumulator;
+pub use amount::{Amount, AmountError};
+pub use consensus::{consensus_scores, ConsensusScores, Score};
+pub use decision::{decide_chain, ChainDecision, ForkDecision, Threshold};
+pub use details::{VoterChoice, VoterDetail};
+pub use duplicates::{DuplicatePolicy, DuplicateVoter};
+pub use error::VoteError;
+pub use merkle::{MerkleProof, MerkleTree, MerkleVote};
+pub use multichain::{ChainTotal, ChainVote, MultiChainTally};
+pub use parallelism::{ExecutionInfo, ExecutionMode};
+pub use period::{
+    Clock, CompetingChains, FinalizedPeriod, PeriodEvent, PeriodSchedule, PeriodStatus, PeriodVote,
+    VotingPeriod,
+};
+pub use power::TallyMode;
+pub use processor::{ChunkResult, VoteData, VoteProcessor};
+pub use quota::{QuotaLimits, QuotaRejection, QuotaUsage, QuotaViolation, VoteQuotas};
+pub use signature::SignedVote;
+pub use tally::VoteTally;
+pub use validation::{CandidateVote, RejectionReason, ValidationReport, ValidatorSet};
+pub use wasm::{WasmVoteProcessor, WasmVoteTallyAccumulator, WasmVotingPeriod};
+pub use weighting::{AgeRules, DecayCurve, VoterAge, Weighting};
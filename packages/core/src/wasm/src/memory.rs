@@ -0,0 +1,238 @@
+//! Linear-memory API for large batches.
+//!
+//! JS allocates a buffer in wasm memory with [`allocate`], writes votes into
+//! it in the `serializeVotes` layout and calls `process_votes_at(ptr, len)`.
+//! The votes are decoded in place and the result comes back as a pointer
+//! to a [`RESULT_SIZE`]-byte [`RawTally`], so neither the input bytes nor a
+//! result object cross the boundary. Both buffers are released with
+//! [`deallocate`], following the convention of `crypto/src/simd.ts`.
+
+use crate::decode::decode_votes;
+use crate::error::VoteError;
+use crate::processor::VoteProcessor;
+use crate::tally::VoteTally;
+use std::alloc::{self, Layout};
+
+/// Alignment of every buffer handed out by [`allocate`].
+pub const ALIGN: usize = 8;
+
+/// Size in bytes of an encoded [`RawTally`].
+pub const RESULT_SIZE: usize = 48;
+
+fn layout(size: usize) -> Option<Layout> {
+    Layout::from_size_align(size, ALIGN).ok()
+}
+
+/// Allocates `size` bytes. Returns null when `size` is zero or the memory
+/// cannot be allocated, so `if (!ptr)` detects failure in JS.
+pub fn allocate(size: usize) -> *mut u8 {
+    match layout(size) {
+        // SAFETY: the layout has a non-zero size.
+        Some(layout) if size > 0 => unsafe { alloc::alloc(layout) },
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Frees a buffer from [`allocate`]. Null pointers are ignored.
+///
+/// # Safety
+///
+/// `ptr` must be null or come from [`allocate`] with the same `size`, and
+/// must not be used afterwards.
+pub unsafe fn deallocate(ptr: *mut u8, size: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    if let Some(layout) = layout(size) {
+        alloc::dealloc(ptr, layout);
+    }
+}
+
+/// The fixed-layout result of `process_votes_at`. Encoded little-endian
+/// with no padding:
+///
+/// ```text
+/// offset  0  u128 approved
+/// offset 16  u128 rejected
+/// offset 32  u32  vote count (input votes)
+/// offset 36  u32  total votes (counted under the duplicate policy)
+/// offset 40  u32  unique voters
+/// offset 44  u32  voters with more than one vote
+/// ```
+///
+/// Counts above `u32::MAX` are clamped; chunks are far smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawTally {
+    pub approved: u128,
+    pub rejected: u128,
+    pub vote_count: u32,
+    pub total_votes: u32,
+    pub unique_voters: u32,
+    pub duplicate_voters: u32,
+}
+
+pub(crate) fn clamp(count: usize) -> u32 {
+    u32::try_from(count).unwrap_or(u32::MAX)
+}
+
+impl RawTally {
+    pub fn to_bytes(&self) -> [u8; RESULT_SIZE] {
+        let mut bytes = [0u8; RESULT_SIZE];
+        bytes[0..16].copy_from_slice(&self.approved.to_le_bytes());
+        bytes[16..32].copy_from_slice(&self.rejected.to_le_bytes());
+        bytes[32..36].copy_from_slice(&self.vote_count.to_le_bytes());
+        bytes[36..40].copy_from_slice(&self.total_votes.to_le_bytes());
+        bytes[40..44].copy_from_slice(&self.unique_voters.to_le_bytes());
+        bytes[44..48].copy_from_slice(&self.duplicate_voters.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; RESULT_SIZE]) -> Self {
+        let u128_at = |at: usize| u128::from_le_bytes(bytes[at..at + 16].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        RawTally {
+            approved: u128_at(0),
+            rejected: u128_at(16),
+            vote_count: u32_at(32),
+            total_votes: u32_at(36),
+            unique_voters: u32_at(40),
+            duplicate_voters: u32_at(44),
+        }
+    }
+}
+
+impl VoteProcessor {
+    /// Tallies votes in the `serializeVotes` layout into a [`RawTally`].
+    pub fn raw_tally(&self, bytes: &[u8]) -> Result<RawTally, VoteError> {
+        let votes = decode_votes(bytes)?;
+        let result = self.process_chunk(&votes)?;
+        let tally = VoteTally::from_result(&result, self.duplicate_policy(), votes.len(), 0, 0);
+        Ok(RawTally {
+            approved: result.approved,
+            rejected: result.rejected,
+            vote_count: clamp(votes.len()),
+            total_votes: clamp(tally.total_votes),
+            unique_voters: clamp(tally.unique_voters),
+            duplicate_voters: clamp(result.duplicates.len()),
+        })
+    }
+}
+
+/// Tallies the `len` bytes of votes at `ptr` and returns a new
+/// [`RESULT_SIZE`]-byte buffer holding the [`RawTally`], to be freed with
+/// [`deallocate`]. On error nothing is allocated.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` initialized bytes that stay unchanged during
+/// the call. It may be null when `len` is zero.
+pub unsafe fn process_votes_at(
+    processor: &VoteProcessor,
+    ptr: *const u8,
+    len: usize,
+) -> Result<*mut u8, VoteError> {
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(ptr, len)
+    };
+    let encoded = processor.raw_tally(bytes)?.to_bytes();
+
+    let out = allocate(RESULT_SIZE);
+    if out.is_null() {
+        alloc::handle_alloc_error(layout(RESULT_SIZE).expect("valid result layout"));
+    }
+    std::ptr::copy_nonoverlapping(encoded.as_ptr(), out, RESULT_SIZE);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duplicates::DuplicatePolicy;
+
+    fn encode(votes: &[(i64, i32, &str)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for &(balance, approved, voter) in votes {
+            bytes.extend_from_slice(&balance.to_le_bytes());
+            bytes.extend_from_slice(&approved.to_le_bytes());
+            bytes.extend_from_slice(&(voter.len() as i32).to_le_bytes());
+            bytes.extend_from_slice(voter.as_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn allocates_aligned_buffers() {
+        assert!(allocate(0).is_null());
+        let ptr = allocate(13);
+        assert!(!ptr.is_null());
+        assert_eq!(ptr as usize % ALIGN, 0);
+        unsafe {
+            deallocate(ptr, 13);
+            deallocate(std::ptr::null_mut(), 13);
+        }
+    }
+
+    #[test]
+    fn encodes_fixed_layout() {
+        let raw = RawTally {
+            approved: u128::MAX - 1,
+            rejected: 7,
+            vote_count: 3,
+            total_votes: 2,
+            unique_voters: 2,
+            duplicate_voters: 1,
+        };
+        let bytes = raw.to_bytes();
+        assert_eq!(bytes[16], 7);
+        assert_eq!(
+            &bytes[32..48],
+            &[3, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0]
+        );
+        assert_eq!(RawTally::from_bytes(&bytes), raw);
+    }
+
+    #[test]
+    fn tallies_votes_in_place() {
+        let input = encode(&[(10, 1, "a"), (5, 0, "b"), (7, 0, "a")]);
+        let mut processor = VoteProcessor::new();
+        processor.set_duplicate_policy(DuplicatePolicy::FirstWins);
+
+        let ptr = allocate(input.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(input.as_ptr(), ptr, input.len());
+            let out = process_votes_at(&processor, ptr, input.len()).unwrap();
+            let raw = RawTally::from_bytes(&*(out as *const [u8; RESULT_SIZE]));
+            deallocate(out, RESULT_SIZE);
+            deallocate(ptr, input.len());
+
+            assert_eq!(
+                raw,
+                RawTally {
+                    approved: 10,
+                    rejected: 5,
+                    vote_count: 3,
+                    total_votes: 2,
+                    unique_voters: 2,
+                    duplicate_voters: 1,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn empty_and_malformed_input() {
+        let processor = VoteProcessor::new();
+        unsafe {
+            let out = process_votes_at(&processor, std::ptr::null(), 0).unwrap();
+            let raw = RawTally::from_bytes(&*(out as *const [u8; RESULT_SIZE]));
+            deallocate(out, RESULT_SIZE);
+            assert_eq!(raw.vote_count, 0);
+
+            let input = encode(&[(10, 1, "abc")]);
+            let err = process_votes_at(&processor, input.as_ptr(), input.len() - 1).unwrap_err();
+            assert_eq!(err.code(), "DECODE_ERROR");
+        }
+    }
+}
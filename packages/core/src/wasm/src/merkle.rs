@@ -0,0 +1,292 @@
+//! Votes Merkle root, built the same way as `MerkleTree.createRoot` in
+//! `utils/merkle.ts`.
+//!
+//! Leaves are the hashes of the input strings. Each parent is the hash of
+//! its children's hex strings concatenated, and the last node of an odd
+//! layer is paired with itself. For `votesMerkleRoot` the inputs are
+//! `JSON.stringify({ voteId, voter, timestamp })` for each vote, as in
+//! `DirectVoting.createVoteMerkleRoot`. Proofs follow `generateProof` and
+//! `verifyProof`.
+//!
+//! Nodes are hashed with `HashUtils.sha256` (lowercase hex SHA-256 of the
+//! UTF-8 string). This is the deterministic part of `HybridCrypto.hash`; the
+//! TypeScript tree also mixes in a signature and a Kyber secret from freshly
+//! generated keys, so its roots differ on every run and cannot be reproduced.
+
+use crate::error::VoteError;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The fields of a `Vote` committed to by `votesMerkleRoot`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MerkleVote {
+    pub vote_id: String,
+    pub voter: String,
+    pub timestamp: u64,
+}
+
+impl MerkleVote {
+    /// The leaf input, matching `JSON.stringify({ voteId, voter, timestamp })`.
+    pub fn leaf_data(&self) -> String {
+        // Field order follows the struct, as in the TypeScript object literal.
+        serde_json::to_string(self).expect("MerkleVote serializes to JSON")
+    }
+}
+
+/// `HashUtils.sha256`: lowercase hex SHA-256 of a UTF-8 string.
+pub fn hash_data(data: &str) -> String {
+    hex::encode(Sha256::digest(data.as_bytes()))
+}
+
+/// Parent of two nodes: the hash of their hex strings concatenated.
+pub fn hash_pair(left: &str, right: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/// Inclusion proof for one leaf, as `MerkleProof` in `utils/merkle.ts`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub index: usize,
+    /// The leaf hash.
+    pub hash: String,
+    /// Sibling hashes from the leaf layer up to just below the root.
+    pub siblings: Vec<String>,
+}
+
+/// A Merkle tree with every layer kept, leaves last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// `layers[0]` is the root layer, `layers[len - 1]` the leaves.
+    layers: Vec<Vec<String>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree over `data`, which must be a non-empty list of
+    /// non-empty strings.
+    pub fn new<S: AsRef<str> + Sync>(data: &[S]) -> Result<Self, VoteError> {
+        if data.is_empty() {
+            return Err(VoteError::EmptyMerkleInput);
+        }
+        if let Some(index) = data.iter().position(|item| item.as_ref().is_empty()) {
+            return Err(VoteError::EmptyMerkleLeaf { index });
+        }
+
+        let leaves: Vec<String> = data
+            .par_iter()
+            .map(|item| hash_data(item.as_ref()))
+            .collect();
+        let mut layers = vec![leaves];
+        while layers[0].len() > 1 {
+            let parents = layers[0]
+                .par_chunks(2)
+                .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            layers.insert(0, parents);
+        }
+
+        Ok(MerkleTree { layers })
+    }
+
+    /// Builds the tree over the leaf data of `votes`.
+    pub fn from_votes(votes: &[MerkleVote]) -> Result<Self, VoteError> {
+        let data: Vec<String> = votes.par_iter().map(MerkleVote::leaf_data).collect();
+        Self::new(&data)
+    }
+
+    pub fn root(&self) -> &str {
+        &self.layers[0][0]
+    }
+
+    pub fn leaves(&self) -> &[String] {
+        &self.layers[self.layers.len() - 1]
+    }
+
+    /// Number of layers, including the root and the leaves.
+    pub fn depth(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Proof that leaf `index` is in the tree. A node without a sibling in
+    /// an odd layer is its own sibling, as in `generateProof`.
+    pub fn proof(&self, index: usize) -> Result<MerkleProof, VoteError> {
+        let leaves = self.leaves();
+        if index >= leaves.len() {
+            return Err(VoteError::LeafIndexOutOfRange {
+                index,
+                leaves: leaves.len(),
+            });
+        }
+
+        let mut siblings = Vec::with_capacity(self.depth() - 1);
+        let mut current = index;
+        for layer in self.layers[1..].iter().rev() {
+            let sibling = current ^ 1;
+            siblings.push(layer.get(sibling).unwrap_or(&layer[current]).clone());
+            current /= 2;
+        }
+
+        Ok(MerkleProof {
+            index,
+            hash: leaves[index].clone(),
+            siblings,
+        })
+    }
+}
+
+/// Checks that `data` is the leaf proven by `proof` and that the proof
+/// leads to `root`, as `MerkleTree.verifyProof` does.
+pub fn verify_proof(proof: &MerkleProof, data: &str, root: &str) -> bool {
+    if data.is_empty() || root.is_empty() || hash_data(data) != proof.hash {
+        return false;
+    }
+    // Bits beyond the path length would address a leaf outside the tree.
+    if proof.siblings.len() < usize::BITS as usize && proof.index >> proof.siblings.len() != 0 {
+        return false;
+    }
+
+    let computed =
+        proof
+            .siblings
+            .iter()
+            .enumerate()
+            .fold(proof.hash.clone(), |hash, (level, sibling)| {
+                if (proof.index >> level) & 1 == 1 {
+                    hash_pair(sibling, &hash)
+                } else {
+                    hash_pair(&hash, sibling)
+                }
+            });
+    computed == root
+}
+
+/// The `votesMerkleRoot` of a set of votes.
+pub fn votes_merkle_root(votes: &[MerkleVote]) -> Result<String, VoteError> {
+    Ok(MerkleTree::from_votes(votes)?.root().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote(id: &str) -> MerkleVote {
+        MerkleVote {
+            vote_id: id.to_string(),
+            voter: format!("voter-{id}"),
+            timestamp: 1_700_000_000_000,
+        }
+    }
+
+    #[test]
+    fn leaf_data_matches_json_stringify() {
+        assert_eq!(
+            vote("v1").leaf_data(),
+            r#"{"voteId":"v1","voter":"voter-v1","timestamp":1700000000000}"#
+        );
+    }
+
+    #[test]
+    fn hashes_match_hash_utils_sha256() {
+        assert_eq!(
+            hash_data("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hash_pair("a", "bc"), hash_data("abc"));
+    }
+
+    #[test]
+    fn duplicates_last_node_of_odd_layers() {
+        let tree = MerkleTree::new(&["a", "b", "c"]).unwrap();
+        let (a, b, c) = (hash_data("a"), hash_data("b"), hash_data("c"));
+        let expected = hash_pair(&hash_pair(&a, &b), &hash_pair(&c, &c));
+
+        assert_eq!(tree.root(), expected);
+        assert_eq!(tree.leaves(), [a, b, c]);
+        assert_eq!(tree.depth(), 3);
+    }
+
+    #[test]
+    fn single_leaf_is_its_own_root() {
+        let tree = MerkleTree::new(&["only"]).unwrap();
+        assert_eq!(tree.root(), hash_data("only"));
+        assert_eq!(tree.depth(), 1);
+    }
+
+    #[test]
+    fn votes_root_uses_leaf_data() {
+        let votes = vec![vote("v1"), vote("v2")];
+        let data: Vec<String> = votes.iter().map(MerkleVote::leaf_data).collect();
+        assert_eq!(
+            votes_merkle_root(&votes).unwrap(),
+            MerkleTree::new(&data).unwrap().root()
+        );
+    }
+
+    #[test]
+    fn proves_every_leaf() {
+        for count in 1..=9 {
+            let data: Vec<String> = (0..count).map(|i| format!("item-{i}")).collect();
+            let tree = MerkleTree::new(&data).unwrap();
+            for (index, item) in data.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                assert_eq!(proof.siblings.len(), tree.depth() - 1);
+                assert!(verify_proof(&proof, item, tree.root()), "{count}/{index}");
+            }
+        }
+    }
+
+    #[test]
+    fn odd_leaf_is_its_own_sibling() {
+        let tree = MerkleTree::new(&["a", "b", "c"]).unwrap();
+        let proof = tree.proof(2).unwrap();
+        let (a, b, c) = (hash_data("a"), hash_data("b"), hash_data("c"));
+        assert_eq!(proof.siblings, vec![c.clone(), hash_pair(&a, &b)]);
+        assert_eq!(proof.hash, c);
+    }
+
+    #[test]
+    fn rejects_bad_proofs() {
+        let data = ["a", "b", "c", "d"];
+        let tree = MerkleTree::new(&data).unwrap();
+        let proof = tree.proof(1).unwrap();
+
+        assert!(!verify_proof(&proof, "a", tree.root()));
+        assert!(!verify_proof(&proof, "b", &hash_data("b")));
+
+        let mut moved = proof.clone();
+        moved.index = 0;
+        assert!(!verify_proof(&moved, "b", tree.root()));
+
+        let mut out_of_range = proof.clone();
+        out_of_range.index = 5;
+        assert!(!verify_proof(&out_of_range, "b", tree.root()));
+
+        let mut tampered = proof;
+        tampered.siblings[1] = hash_data("x");
+        assert!(!verify_proof(&tampered, "b", tree.root()));
+
+        assert!(tree.proof(4).is_err());
+    }
+
+    #[test]
+    fn single_leaf_proof_has_no_siblings() {
+        let tree = MerkleTree::new(&["only"]).unwrap();
+        let proof = tree.proof(0).unwrap();
+        assert!(proof.siblings.is_empty());
+        assert!(verify_proof(&proof, "only", tree.root()));
+    }
+
+    #[test]
+    fn rejects_empty_input() {
+        assert!(MerkleTree::new::<&str>(&[]).is_err());
+        assert_eq!(
+            MerkleTree::new(&["a", ""]).unwrap_err(),
+            VoteError::EmptyMerkleLeaf { index: 1 }
+        );
+        assert!(votes_merkle_root(&[]).is_err());
+    }
+}
@@ -0,0 +1,285 @@
+//! Tallies for forks with any number of competing chains, keyed by
+//! `Vote.chainVoteData.targetChainId` rather than a binary `approved` flag.
+
+use crate::decision::ForkDecision;
+use crate::duplicates::{counted_mask, find_duplicates, Ballot, DuplicateVoter};
+use crate::error::VoteError;
+use crate::processor::{try_map_in_order, VoteProcessor};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+
+/// A vote for one of several competing chains, built from a `Vote` and its
+/// `chainVoteData`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainVote {
+    pub voter: String,
+    pub target_chain_id: String,
+    pub fork_height: u64,
+    /// `chainVoteData.amount` as a decimal string.
+    pub amount: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+}
+
+impl Ballot for ChainVote {
+    fn voter(&self) -> &str {
+        &self.voter
+    }
+
+    fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainTotal {
+    pub chain_id: String,
+    /// Counted vote power. Serialized to JS as a `BigInt`.
+    pub power: u128,
+    /// Distinct voters whose counted votes back this chain.
+    pub voters: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MultiChainTally {
+    pub fork_height: u64,
+    /// One entry per chain that received a vote, ordered by chain ID.
+    pub chains: Vec<ChainTotal>,
+    /// Chain with the most power; `None` when there were no votes.
+    pub winner: Option<String>,
+    pub duplicates: Vec<DuplicateVoter>,
+}
+
+impl MultiChainTally {
+    /// The `ForkDecision` record for the winning chain, with every chain's
+    /// power in `votePowers`. `None` when there is no winner.
+    pub fn fork_decision(&self, decided_at: u64) -> Option<ForkDecision> {
+        Some(ForkDecision {
+            selected_chain: self.winner.clone()?,
+            vote_powers: self
+                .chains
+                .iter()
+                .map(|chain| (chain.chain_id.clone(), chain.power.to_string()))
+                .collect(),
+            decided_at,
+            fork_height: self.fork_height,
+        })
+    }
+}
+
+/// Picks the winning chain. Ties on power go to the chain with more voters,
+/// then to the lexicographically smallest chain ID, so every node agrees.
+pub fn select_winner(chains: &[ChainTotal]) -> Option<&ChainTotal> {
+    chains
+        .iter()
+        .max_by_key(|chain| (chain.power, chain.voters, Reverse(chain.chain_id.as_str())))
+}
+
+impl VoteProcessor {
+    /// Tallies votes for the fork at `fork_height`, summing power per target
+    /// chain with the processor's tally mode and duplicate policy. Votes cast
+    /// for a different fork height are an error.
+    pub fn tally_chains(
+        &self,
+        votes: &[ChainVote],
+        fork_height: u64,
+    ) -> Result<MultiChainTally, VoteError> {
+        self.check_chunk_size(votes.len())?;
+        if let Some((index, vote)) = votes
+            .iter()
+            .enumerate()
+            .find(|(_, vote)| vote.fork_height != fork_height)
+        {
+            return Err(VoteError::ForkHeightMismatch {
+                index,
+                voter: vote.voter.clone(),
+                fork_height: vote.fork_height,
+                expected: fork_height,
+            });
+        }
+
+        let duplicates = find_duplicates(votes);
+        let counted = counted_mask(votes, &duplicates, self.duplicate_policy())?;
+        let weights = try_map_in_order(votes, |index, vote| {
+            self.balance_weight(index, &vote.amount, &vote.voter, vote.timestamp)
+        })?;
+
+        let mut totals: BTreeMap<&str, (u128, HashSet<&str>)> = BTreeMap::new();
+        for (index, ((vote, weight), _)) in votes
+            .iter()
+            .zip(weights)
+            .zip(&counted)
+            .enumerate()
+            .filter(|(_, (_, &counted))| counted)
+        {
+            let (power, voters) = totals.entry(vote.target_chain_id.as_str()).or_default();
+            *power = power
+                .checked_add(weight)
+                .ok_or_else(|| VoteError::Overflow {
+                    tally: vote.target_chain_id.clone(),
+                    index: Some(index),
+                    voter: Some(vote.voter.clone()),
+                })?;
+            voters.insert(vote.voter.as_str());
+        }
+
+        let chains: Vec<ChainTotal> = totals
+            .into_iter()
+            .map(|(chain_id, (power, voters))| ChainTotal {
+                chain_id: chain_id.to_string(),
+                power,
+                voters: voters.len(),
+            })
+            .collect();
+        let winner = select_winner(&chains).map(|chain| chain.chain_id.clone());
+
+        Ok(MultiChainTally {
+            fork_height,
+            chains,
+            winner,
+            duplicates,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duplicates::DuplicatePolicy;
+
+    fn vote(voter: &str, chain: &str, amount: &str) -> ChainVote {
+        ChainVote {
+            voter: voter.to_string(),
+            target_chain_id: chain.to_string(),
+            fork_height: 100,
+            amount: amount.to_string(),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn fresh_coins_cannot_swing_the_vote() {
+        use crate::weighting::{AgeRules, VoterAge, Weighting, MATURITY_PERIOD};
+
+        let now = 10 * MATURITY_PERIOD;
+        let mut late = vote("b", "chain-b", "100");
+        late.timestamp = Some(now - MATURITY_PERIOD);
+        let votes = vec![
+            vote("a", "chain-a", "60"),
+            late,
+            vote("whale", "chain-b", "1000"),
+        ];
+        let ages = ["a", "b", "whale"].map(|voter| VoterAge {
+            voter: voter.to_string(),
+            account_age: Some(20_000),
+            coin_age: Some(if voter == "whale" { 60_000 } else { now }),
+        });
+        let mut processor = VoteProcessor::new();
+        assert_eq!(
+            processor
+                .tally_chains(&votes, 100)
+                .unwrap()
+                .winner
+                .as_deref(),
+            Some("chain-b")
+        );
+
+        processor.set_weighting(Some(
+            Weighting::new(AgeRules::default(), now, ages).unwrap(),
+        ));
+        let tally = processor.tally_chains(&votes, 100).unwrap();
+        let power: Vec<_> = tally
+            .chains
+            .iter()
+            .map(|c| (c.chain_id.as_str(), c.power))
+            .collect();
+        assert_eq!(power, vec![("chain-a", 60), ("chain-b", 50)]);
+        assert_eq!(tally.winner.as_deref(), Some("chain-a"));
+    }
+
+    #[test]
+    fn totals_power_per_chain() {
+        let votes = vec![
+            vote("a", "chain-b", "30"),
+            vote("b", "chain-a", "25"),
+            vote("c", "chain-c", "40"),
+            vote("d", "chain-b", "15"),
+        ];
+        let tally = VoteProcessor::new().tally_chains(&votes, 100).unwrap();
+
+        let summary: Vec<_> = tally
+            .chains
+            .iter()
+            .map(|c| (c.chain_id.as_str(), c.power, c.voters))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![("chain-a", 25, 1), ("chain-b", 45, 2), ("chain-c", 40, 1)]
+        );
+        assert_eq!(tally.winner.as_deref(), Some("chain-b"));
+
+        let decision = tally.fork_decision(7).unwrap();
+        assert_eq!(decision.selected_chain, "chain-b");
+        assert_eq!(decision.vote_powers.len(), 3);
+        assert_eq!(decision.vote_powers["chain-c"], "40");
+        assert_eq!(decision.fork_height, 100);
+    }
+
+    #[test]
+    fn breaks_ties_by_voters_then_chain_id() {
+        let votes = vec![
+            vote("a", "z", "10"),
+            vote("b", "y", "5"),
+            vote("c", "y", "5"),
+        ];
+        let tally = VoteProcessor::new().tally_chains(&votes, 100).unwrap();
+        assert_eq!(tally.winner.as_deref(), Some("y"));
+
+        let votes = vec![vote("a", "z", "10"), vote("b", "x", "10")];
+        let tally = VoteProcessor::new().tally_chains(&votes, 100).unwrap();
+        assert_eq!(tally.winner.as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn applies_duplicate_policy() {
+        let votes = vec![vote("a", "x", "10"), vote("a", "y", "50")];
+        let mut processor = VoteProcessor::new();
+        processor.set_duplicate_policy(DuplicatePolicy::FirstWins);
+
+        let tally = processor.tally_chains(&votes, 100).unwrap();
+        assert_eq!(tally.chains.len(), 1);
+        assert_eq!(tally.winner.as_deref(), Some("x"));
+        assert_eq!(tally.duplicates[0].indices, vec![0, 1]);
+    }
+
+    #[test]
+    fn rejects_other_fork_heights() {
+        let mut stray = vote("b", "x", "1");
+        stray.fork_height = 99;
+        let err = VoteProcessor::new()
+            .tally_chains(&[vote("a", "x", "1"), stray], 100)
+            .unwrap_err();
+        assert_eq!((err.code(), err.index()), ("FORK_HEIGHT_MISMATCH", Some(1)));
+        assert!(err.to_string().contains("fork height 99"), "{err}");
+    }
+
+    #[test]
+    fn compares_full_fork_heights() {
+        // A `u32` argument would have wrapped this to 100.
+        let err = VoteProcessor::new()
+            .tally_chains(&[vote("a", "x", "1")], (1 << 32) + 100)
+            .unwrap_err();
+        assert_eq!(err.code(), "FORK_HEIGHT_MISMATCH");
+    }
+
+    #[test]
+    fn no_votes_has_no_winner() {
+        let tally = VoteProcessor::new().tally_chains(&[], 100).unwrap();
+        assert!(tally.winner.is_none());
+        assert!(tally.fork_decision(0).is_none());
+    }
+}
@@ -0,0 +1,260 @@
+//! Node-API bindings (the `node` feature).
+//!
+//! Exposes the same core as [`crate::wasm`] to Node.js as a native addon,
+//! so the node process can tally without fetching a `.wasm` file. Tallies
+//! are returned as `BigInt`s and votes can be passed as a `Buffer` in the
+//! `serializeVotes` layout. Errors are thrown as `Error`s named
+//! `VoteProcessorError` with the same `code`, `index` and `voter` properties
+//! as the wasm build.
+
+use crate::details::VoterDetail;
+use crate::duplicates::DuplicateVoter;
+use crate::error::VoteError;
+use crate::memory::clamp;
+use crate::parallelism;
+use crate::processor::{ChunkResult, VoteData, VoteProcessor};
+use crate::tally::VoteTally;
+use crate::wasm::integer_arg;
+use napi::bindgen_prelude::{BigInt, Buffer};
+use napi::{Env, Status};
+use napi_derive::napi;
+
+/// Builds the JS error for `error`, falling back to a plain `Error` if the
+/// properties cannot be set.
+fn to_napi_error(env: &Env, error: VoteError) -> napi::Error {
+    let message = error.to_string();
+    let build = || -> napi::Result<napi::Error> {
+        let mut js_error = env.create_error(napi::Error::new(Status::GenericFailure, &message))?;
+        js_error.set_named_property("name", "VoteProcessorError")?;
+        js_error.set_named_property("code", error.code())?;
+        js_error.set_named_property("index", error.index().map(clamp))?;
+        js_error.set_named_property("voter", error.voter())?;
+        Ok(napi::Error::from(js_error.into_unknown()))
+    };
+    build().unwrap_or_else(|_| napi::Error::new(Status::GenericFailure, message))
+}
+
+#[napi(object, js_name = "VoteData")]
+pub struct NodeVoteData {
+    pub balance: String,
+    pub approved: i32,
+    pub voter: String,
+    pub timestamp: Option<f64>,
+}
+
+/// Fails with `INVALID_ARGUMENT` for a `timestamp` that is not a
+/// non-negative integer, as deserializing the vote does in the wasm build.
+impl TryFrom<NodeVoteData> for VoteData {
+    type Error = VoteError;
+
+    fn try_from(vote: NodeVoteData) -> Result<Self, VoteError> {
+        Ok(VoteData {
+            timestamp: vote
+                .timestamp
+                .map(|timestamp| integer_arg("timestamp", timestamp))
+                .transpose()?,
+            balance: vote.balance,
+            approved: vote.approved,
+            voter: vote.voter,
+        })
+    }
+}
+
+fn to_votes(env: &Env, votes: Vec<NodeVoteData>) -> napi::Result<Vec<VoteData>> {
+    votes
+        .into_iter()
+        .map(VoteData::try_from)
+        .collect::<Result<_, _>>()
+        .map_err(|e| to_napi_error(env, e))
+}
+
+#[napi(object, js_name = "DuplicateVoter")]
+pub struct NodeDuplicateVoter {
+    pub voter: String,
+    pub indices: Vec<u32>,
+}
+
+impl From<DuplicateVoter> for NodeDuplicateVoter {
+    fn from(duplicate: DuplicateVoter) -> Self {
+        NodeDuplicateVoter {
+            voter: duplicate.voter,
+            indices: duplicate.indices.into_iter().map(clamp).collect(),
+        }
+    }
+}
+
+#[napi(object, js_name = "VoterDetail")]
+pub struct NodeVoterDetail {
+    pub voter: String,
+    pub vote_count: u32,
+    pub balance: BigInt,
+    pub power: BigInt,
+    /// `"approve"`, `"reject"` or `"split"`.
+    pub choice: String,
+}
+
+impl From<VoterDetail> for NodeVoterDetail {
+    fn from(detail: VoterDetail) -> Self {
+        NodeVoterDetail {
+            voter: detail.voter,
+            vote_count: clamp(detail.vote_count),
+            balance: detail.balance.into(),
+            power: detail.power.into(),
+            choice: detail.choice.as_str().to_string(),
+        }
+    }
+}
+
+#[napi(object, js_name = "ChunkResult")]
+pub struct NodeChunkResult {
+    pub approved: BigInt,
+    pub rejected: BigInt,
+    pub voters: Vec<String>,
+    pub duplicates: Vec<NodeDuplicateVoter>,
+    pub details: Option<Vec<NodeVoterDetail>>,
+}
+
+impl From<ChunkResult> for NodeChunkResult {
+    fn from(result: ChunkResult) -> Self {
+        NodeChunkResult {
+            approved: result.approved.into(),
+            rejected: result.rejected.into(),
+            voters: result.voters,
+            duplicates: result.duplicates.into_iter().map(Into::into).collect(),
+            details: result
+                .details
+                .map(|details| details.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+#[napi(object, js_name = "VoteTally")]
+pub struct NodeVoteTally {
+    pub approved: BigInt,
+    pub rejected: BigInt,
+    pub total_votes: u32,
+    pub unique_voters: u32,
+    pub participation_rate: f64,
+    pub timestamp: f64,
+}
+
+impl From<VoteTally> for NodeVoteTally {
+    fn from(tally: VoteTally) -> Self {
+        NodeVoteTally {
+            approved: tally.approved.into(),
+            rejected: tally.rejected.into(),
+            total_votes: clamp(tally.total_votes),
+            unique_voters: clamp(tally.unique_voters),
+            participation_rate: tally.participation_rate,
+            timestamp: tally.timestamp as f64,
+        }
+    }
+}
+
+#[napi(js_name = "VoteProcessor")]
+pub struct NodeVoteProcessor {
+    inner: VoteProcessor,
+}
+
+impl Default for NodeVoteProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[napi]
+impl NodeVoteProcessor {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        NodeVoteProcessor {
+            inner: VoteProcessor::new(),
+        }
+    }
+
+    /// `"balance"` (default) or `"quadratic"`.
+    #[napi]
+    pub fn set_tally_mode(&mut self, env: Env, mode: String) -> napi::Result<()> {
+        let mode = mode.parse().map_err(|e| to_napi_error(&env, e))?;
+        self.inner.set_tally_mode(mode);
+        Ok(())
+    }
+
+    /// `"reject"`, `"first-wins"` (default), `"last-wins"` or `"sum"`.
+    #[napi]
+    pub fn set_duplicate_policy(&mut self, env: Env, policy: String) -> napi::Result<()> {
+        let policy = policy.parse().map_err(|e| to_napi_error(&env, e))?;
+        self.inner.set_duplicate_policy(policy);
+        Ok(())
+    }
+
+    /// Throws `INVALID_ARGUMENT` unless `chunk_size` is a non-negative
+    /// integer.
+    #[napi]
+    pub fn set_chunk_size(&mut self, env: Env, chunk_size: f64) -> napi::Result<()> {
+        let chunk_size =
+            integer_arg("chunkSize", chunk_size).map_err(|e| to_napi_error(&env, e))?;
+        self.inner
+            .set_chunk_size(usize::try_from(chunk_size).unwrap_or(usize::MAX));
+        Ok(())
+    }
+
+    /// Adds per-voter `details` to results.
+    #[napi]
+    pub fn set_detailed(&mut self, detailed: bool) {
+        self.inner.set_detailed(detailed);
+    }
+
+    #[napi]
+    pub fn process_vote_chunk(
+        &self,
+        env: Env,
+        votes: Vec<NodeVoteData>,
+    ) -> napi::Result<NodeChunkResult> {
+        let votes = to_votes(&env, votes)?;
+        let result = self
+            .inner
+            .process_chunk(&votes)
+            .map_err(|e| to_napi_error(&env, e))?;
+        Ok(result.into())
+    }
+
+    /// Tallies votes packed by `serializeVotes` in `vote-processor.ts`.
+    #[napi]
+    pub fn process_vote_bytes(&self, env: Env, data: Buffer) -> napi::Result<NodeChunkResult> {
+        let result = self
+            .inner
+            .process_bytes(&data)
+            .map_err(|e| to_napi_error(&env, e))?;
+        Ok(result.into())
+    }
+
+    /// See `WasmVoteProcessor::tally_votes`.
+    #[napi]
+    pub fn tally_votes(
+        &self,
+        env: Env,
+        votes: Vec<NodeVoteData>,
+        eligible_voters: f64,
+        timestamp: f64,
+    ) -> napi::Result<NodeVoteTally> {
+        let votes = to_votes(&env, votes)?;
+        let eligible_voters =
+            integer_arg("eligibleVoters", eligible_voters).map_err(|e| to_napi_error(&env, e))?;
+        let timestamp = integer_arg("timestamp", timestamp).map_err(|e| to_napi_error(&env, e))?;
+        let tally = self
+            .inner
+            .tally_votes(&votes, eligible_voters, timestamp)
+            .map_err(|e| to_napi_error(&env, e))?;
+        Ok(tally.into())
+    }
+}
+
+/// `"parallel"` or `"sequential"`. Native builds use one OS thread per core.
+#[napi]
+pub fn execution_mode() -> String {
+    match parallelism::execution_info().mode {
+        parallelism::ExecutionMode::Parallel => "parallel",
+        parallelism::ExecutionMode::Sequential => "sequential",
+    }
+    .to_string()
+}
@@ -0,0 +1,54 @@
+//! Reports whether tallies run on several threads or sequentially.
+//!
+//! Natively rayon uses OS threads. On `wasm32` there are no threads unless
+//! the crate is built with the `threads` feature and JS has called
+//! `initThreadPool`. Asking rayon before then settles it on the calling
+//! thread for good, so in that build `execution_mode` must only be called
+//! once `initThreadPool` has resolved.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionMode {
+    Sequential,
+    Parallel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionInfo {
+    pub mode: ExecutionMode,
+    pub threads: usize,
+}
+
+pub fn execution_info() -> ExecutionInfo {
+    // Without the worker pool rayon runs everything on the calling thread.
+    if cfg!(all(target_arch = "wasm32", not(feature = "threads"))) {
+        return ExecutionInfo {
+            mode: ExecutionMode::Sequential,
+            threads: 1,
+        };
+    }
+
+    let threads = rayon::current_num_threads();
+    ExecutionInfo {
+        mode: if threads > 1 {
+            ExecutionMode::Parallel
+        } else {
+            ExecutionMode::Sequential
+        },
+        threads,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_rayon_threads_natively() {
+        let info = execution_info();
+        assert_eq!(info.threads, rayon::current_num_threads());
+        assert_eq!(info.mode == ExecutionMode::Parallel, info.threads > 1);
+    }
+}
@@ -0,0 +1,652 @@
+//! Voting period lifecycle, as `VotingPeriod.status` in `models/vote.model.ts`.
+//!
+//! A period starts `active`, accepts votes until its end height or end time
+//! is reached, and then becomes `completed`. It can be `cancelled` while
+//! active. Both end states are final. Block-height and time events drive the
+//! transitions, so every node that sees the same events finishes the period
+//! at the same point with the same tally and votes Merkle root.
+//!
+//! [`VotingPeriod::for_fork`] starts the chain-selection period that
+//! `DirectVotingUtil.initializeChainVotingPeriod` builds.
+
+use crate::accumulator::VoteTallyAccumulator;
+use crate::error::VoteError;
+use crate::merkle::{hash_data, votes_merkle_root, MerkleVote};
+use crate::processor::{VoteData, VoteProcessor};
+use crate::quota::{QuotaLimits, QuotaRejection, QuotaUsage, VoteQuotas};
+use crate::tally::VoteTally;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// `VOTING_CONSTANTS.VOTING_PERIOD_BLOCKS`: about two years of 5-minute blocks.
+pub const VOTING_PERIOD_BLOCKS: u64 = 105_120;
+/// `VOTING_CONSTANTS.VOTING_PERIOD_MS`: about two years.
+pub const VOTING_PERIOD_MS: u64 = 63_072_000_000;
+/// `MINING.MAX_FORK_DEPTH`: the deepest fork a chain-selection vote may
+/// resolve.
+pub const MAX_FORK_DEPTH: u64 = 100;
+/// `CONSENSUS.CONSENSUS_TIMEOUT`: how long a chain-selection period runs.
+pub const CONSENSUS_TIMEOUT_MS: u64 = 30 * 60 * 1000;
+
+/// Source of the current time in milliseconds. Implemented for closures, so
+/// callers pass `|| now` or a wrapper around their own clock.
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+impl<F: Fn() -> u64> Clock for F {
+    fn now(&self) -> u64 {
+        self()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeriodStatus {
+    Active,
+    Completed,
+    Cancelled,
+}
+
+impl fmt::Display for PeriodStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PeriodStatus::Active => "active",
+            PeriodStatus::Completed => "completed",
+            PeriodStatus::Cancelled => "cancelled",
+        })
+    }
+}
+
+/// Something that happened on the chain or the clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PeriodEvent {
+    /// A block at `height` was connected.
+    Block { height: u64 },
+    /// The clock reached `timestamp` milliseconds.
+    Time { timestamp: u64 },
+    /// The period is abandoned, e.g. because the network became unstable.
+    Cancel,
+}
+
+impl PeriodEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            PeriodEvent::Block { .. } => "block",
+            PeriodEvent::Time { .. } => "time",
+            PeriodEvent::Cancel => "cancel",
+        }
+    }
+}
+
+/// Where a period starts and when it is due to end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodSchedule {
+    pub start_height: u64,
+    /// The period completes at the first block at or above this height.
+    pub end_height: u64,
+    pub start_time: u64,
+    /// The period completes at the first time event at or after this time.
+    pub end_time: u64,
+}
+
+impl PeriodSchedule {
+    /// A period of [`VOTING_PERIOD_BLOCKS`] and [`VOTING_PERIOD_MS`] from
+    /// `height` and `time`, as in `startVotingPeriod`.
+    pub fn starting_at(height: u64, time: u64) -> Self {
+        PeriodSchedule {
+            start_height: height,
+            end_height: height.saturating_add(VOTING_PERIOD_BLOCKS),
+            start_time: time,
+            end_time: time.saturating_add(VOTING_PERIOD_MS),
+        }
+    }
+}
+
+/// The chains competing after a fork, as `VotingPeriod.competingChains`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompetingChains {
+    pub old_chain_id: String,
+    pub new_chain_id: String,
+    /// The fork height.
+    pub common_ancestor_height: u64,
+}
+
+impl CompetingChains {
+    /// The ID of the chain-selection period for this fork: the first 53 bits
+    /// of the SHA-256 of the JSON-encoded fork, so it is the same on every
+    /// node and stays an exact JS number. `initializeChainVotingPeriod`
+    /// uses `Date.now()`, which differs between nodes and can collide.
+    pub fn period_id(&self) -> u64 {
+        let json = serde_json::to_string(self).expect("CompetingChains serializes to JSON");
+        let hash = hash_data(&json);
+        u64::from_str_radix(&hash[..16], 16).expect("SHA-256 hex digest") >> 11
+    }
+}
+
+/// A vote cast in a period: the fields tallied plus those committed to by
+/// `votesMerkleRoot`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriodVote {
+    pub vote_id: String,
+    pub voter: String,
+    pub balance: String,
+    pub approved: i32,
+    pub timestamp: u64,
+    /// Block height the vote was cast at, for the quota cooldown. Defaults
+    /// to the latest height the period has seen.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u64>,
+}
+
+impl PeriodVote {
+    fn vote_data(&self) -> VoteData {
+        VoteData {
+            balance: self.balance.clone(),
+            approved: self.approved,
+            voter: self.voter.clone(),
+            timestamp: Some(self.timestamp),
+        }
+    }
+
+    fn merkle_vote(&self) -> MerkleVote {
+        MerkleVote {
+            vote_id: self.vote_id.clone(),
+            voter: self.voter.clone(),
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// A period that has reached a final status.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FinalizedPeriod {
+    pub period_id: u64,
+    /// `Completed` or `Cancelled`.
+    pub status: PeriodStatus,
+    pub start_height: u64,
+    /// Height of the last block seen, as `endBlock` in `finalizePeriod`.
+    pub end_height: u64,
+    pub start_time: u64,
+    /// Time of the last time event seen, or `start_time` if there was none.
+    pub end_time: u64,
+    /// Tally at `end_time`.
+    pub tally: VoteTally,
+    /// Root over every vote added, in order; empty when there were none,
+    /// as in `initializeNewPeriod`.
+    pub votes_merkle_root: String,
+    /// Set for chain-selection periods.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub competing_chains: Option<CompetingChains>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VotingPeriod {
+    period_id: u64,
+    schedule: PeriodSchedule,
+    status: PeriodStatus,
+    /// Latest block height and time seen; events may not go back.
+    height: u64,
+    time: u64,
+    eligible_voters: u64,
+    accumulator: VoteTallyAccumulator,
+    merkle_votes: Vec<MerkleVote>,
+    quotas: VoteQuotas,
+    competing_chains: Option<CompetingChains>,
+}
+
+impl VotingPeriod {
+    /// Starts an active period that tallies with `processor`'s settings and
+    /// measures participation against `eligible_voters`. Votes are subject
+    /// to the default [`QuotaLimits`].
+    pub fn new(
+        period_id: u64,
+        schedule: PeriodSchedule,
+        processor: &VoteProcessor,
+        eligible_voters: u64,
+    ) -> Self {
+        VotingPeriod {
+            period_id,
+            schedule,
+            status: PeriodStatus::Active,
+            height: schedule.start_height,
+            time: schedule.start_time,
+            eligible_voters,
+            accumulator: processor.accumulator(),
+            merkle_votes: Vec::new(),
+            quotas: VoteQuotas::default(),
+            competing_chains: None,
+        }
+    }
+
+    /// Starts the chain-selection period for `chains` at `current_height`.
+    /// It runs for [`VOTING_PERIOD_BLOCKS`] blocks or [`CONSENSUS_TIMEOUT_MS`]
+    /// from `clock`'s current time, and its ID is
+    /// [`CompetingChains::period_id`].
+    ///
+    /// Fails with [`VoteError::ForkTooDeep`] when the fork is more than
+    /// [`MAX_FORK_DEPTH`] blocks below `current_height`. A fork height above
+    /// `current_height` has depth zero.
+    pub fn for_fork(
+        chains: CompetingChains,
+        current_height: u64,
+        processor: &VoteProcessor,
+        eligible_voters: u64,
+        clock: &impl Clock,
+    ) -> Result<Self, VoteError> {
+        let fork_height = chains.common_ancestor_height;
+        let depth = current_height.saturating_sub(fork_height);
+        if depth > MAX_FORK_DEPTH {
+            return Err(VoteError::ForkTooDeep {
+                fork_height,
+                current_height,
+                depth,
+                max: MAX_FORK_DEPTH,
+            });
+        }
+
+        let now = clock.now();
+        let schedule = PeriodSchedule {
+            start_height: current_height,
+            end_height: current_height.saturating_add(VOTING_PERIOD_BLOCKS),
+            start_time: now,
+            end_time: now.saturating_add(CONSENSUS_TIMEOUT_MS),
+        };
+        let mut period =
+            VotingPeriod::new(chains.period_id(), schedule, processor, eligible_voters);
+        period.competing_chains = Some(chains);
+        Ok(period)
+    }
+
+    pub fn period_id(&self) -> u64 {
+        self.period_id
+    }
+
+    pub fn schedule(&self) -> PeriodSchedule {
+        self.schedule
+    }
+
+    pub fn status(&self) -> PeriodStatus {
+        self.status
+    }
+
+    pub fn competing_chains(&self) -> Option<&CompetingChains> {
+        self.competing_chains.as_ref()
+    }
+
+    /// Replaces the quota limits. Votes already admitted keep counting
+    /// against the new limits.
+    pub fn set_quota_limits(&mut self, limits: QuotaLimits) {
+        self.quotas.set_limits(limits);
+    }
+
+    /// Quota usage of every voter with an admitted vote, sorted by voter.
+    pub fn quota_usage(&self) -> Vec<QuotaUsage> {
+        self.quotas.usage()
+    }
+
+    /// Adds votes while the period is active. Votes over a voter's quota
+    /// are left out of the tally and the votes Merkle root, and returned
+    /// with the reason. On error the period is left unchanged.
+    pub fn add_votes(&mut self, votes: &[PeriodVote]) -> Result<Vec<QuotaRejection>, VoteError> {
+        if self.status != PeriodStatus::Active {
+            return Err(VoteError::InactivePeriod {
+                period_id: self.period_id,
+                status: self.status,
+            });
+        }
+        let quota_votes: Vec<_> = votes
+            .iter()
+            .map(|vote| {
+                let height = vote.height.unwrap_or(self.height);
+                (vote.voter.as_str(), vote.timestamp, height)
+            })
+            .collect();
+        let pass = self.quotas.check(&quota_votes);
+        let admitted: Vec<&PeriodVote> = votes
+            .iter()
+            .zip(&pass.admitted)
+            .filter_map(|(vote, &admitted)| admitted.then_some(vote))
+            .collect();
+
+        let data: Vec<VoteData> = admitted.iter().map(|vote| vote.vote_data()).collect();
+        self.accumulator.add_chunk(&data)?;
+        self.merkle_votes
+            .extend(admitted.iter().map(|vote| vote.merkle_vote()));
+        let rejected = pass.rejected.clone();
+        self.quotas.commit(pass);
+        Ok(rejected)
+    }
+
+    /// Applies `event`. Returns the finalized period when the event ends
+    /// it. Events for a period that has already ended, and heights or times
+    /// earlier than ones already seen, are rejected and change nothing.
+    pub fn apply(&mut self, event: PeriodEvent) -> Result<Option<FinalizedPeriod>, VoteError> {
+        if self.status != PeriodStatus::Active {
+            return Err(VoteError::InvalidTransition {
+                period_id: self.period_id,
+                status: self.status,
+                event: event.name(),
+            });
+        }
+
+        let next = match event {
+            PeriodEvent::Block { height } => {
+                self.check_order("height", height, self.height)?;
+                self.height = height;
+                (height >= self.schedule.end_height).then_some(PeriodStatus::Completed)
+            }
+            PeriodEvent::Time { timestamp } => {
+                self.check_order("time", timestamp, self.time)?;
+                self.time = timestamp;
+                (timestamp >= self.schedule.end_time).then_some(PeriodStatus::Completed)
+            }
+            PeriodEvent::Cancel => Some(PeriodStatus::Cancelled),
+        };
+
+        match next {
+            Some(status) => {
+                self.status = status;
+                self.finalized().map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn check_order(&self, clock: &'static str, value: u64, last: u64) -> Result<(), VoteError> {
+        if value < last {
+            return Err(VoteError::StaleEvent {
+                period_id: self.period_id,
+                clock,
+                value,
+                last,
+            });
+        }
+        Ok(())
+    }
+
+    fn finalized(&self) -> Result<FinalizedPeriod, VoteError> {
+        let votes_merkle_root = if self.merkle_votes.is_empty() {
+            String::new()
+        } else {
+            votes_merkle_root(&self.merkle_votes)?
+        };
+        Ok(FinalizedPeriod {
+            period_id: self.period_id,
+            status: self.status,
+            start_height: self.schedule.start_height,
+            end_height: self.height,
+            start_time: self.schedule.start_time,
+            end_time: self.time,
+            tally: self.accumulator.tally(self.eligible_voters, self.time),
+            votes_merkle_root,
+            competing_chains: self.competing_chains.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: u64 = 1_700_000_000_000;
+
+    fn schedule() -> PeriodSchedule {
+        PeriodSchedule {
+            start_height: 100,
+            end_height: 110,
+            start_time: START,
+            end_time: START + 1_000,
+        }
+    }
+
+    fn period() -> VotingPeriod {
+        VotingPeriod::new(7, schedule(), &VoteProcessor::new(), 4)
+    }
+
+    fn vote(id: &str, voter: &str, balance: &str, approved: i32) -> PeriodVote {
+        PeriodVote {
+            vote_id: id.to_string(),
+            voter: voter.to_string(),
+            balance: balance.to_string(),
+            approved,
+            timestamp: START + 10,
+            height: None,
+        }
+    }
+
+    #[test]
+    fn default_schedule_uses_voting_constants() {
+        let schedule = PeriodSchedule::starting_at(5, 1_000);
+        assert_eq!(schedule.end_height, 5 + VOTING_PERIOD_BLOCKS);
+        assert_eq!(schedule.end_time, 1_000 + VOTING_PERIOD_MS);
+    }
+
+    #[test]
+    fn completes_at_end_height_with_tally_and_root() {
+        let mut period = period();
+        let votes = vec![vote("1", "a", "10", 1), vote("2", "b", "5", 0)];
+        period.add_votes(&votes).unwrap();
+
+        assert_eq!(
+            period.apply(PeriodEvent::Block { height: 105 }).unwrap(),
+            None
+        );
+        assert_eq!(
+            period
+                .apply(PeriodEvent::Time {
+                    timestamp: START + 500
+                })
+                .unwrap(),
+            None
+        );
+        let finalized = period
+            .apply(PeriodEvent::Block { height: 110 })
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(period.status(), PeriodStatus::Completed);
+        assert_eq!(finalized.status, PeriodStatus::Completed);
+        assert_eq!(
+            (finalized.end_height, finalized.end_time),
+            (110, START + 500)
+        );
+        assert_eq!(
+            (finalized.tally.approved, finalized.tally.rejected),
+            (10, 5)
+        );
+        assert_eq!(finalized.tally.participation_rate, 0.5);
+        let merkle_votes: Vec<MerkleVote> = votes.iter().map(PeriodVote::merkle_vote).collect();
+        assert_eq!(
+            finalized.votes_merkle_root,
+            votes_merkle_root(&merkle_votes).unwrap()
+        );
+    }
+
+    #[test]
+    fn completes_at_end_time() {
+        let mut period = period();
+        let finalized = period
+            .apply(PeriodEvent::Time {
+                timestamp: START + 1_000,
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(finalized.end_height, 100);
+        assert_eq!(finalized.votes_merkle_root, "");
+        assert_eq!(finalized.tally.total_votes, 0);
+    }
+
+    #[test]
+    fn only_active_periods_accept_votes_and_events() {
+        let mut period = period();
+        let finalized = period.apply(PeriodEvent::Cancel).unwrap().unwrap();
+        assert_eq!(finalized.status, PeriodStatus::Cancelled);
+
+        let err = period.add_votes(&[vote("1", "a", "1", 1)]).unwrap_err();
+        assert_eq!(err.code(), "INACTIVE_PERIOD");
+        assert_eq!(err.to_string(), "Voting period 7 is cancelled, not active");
+
+        let err = period
+            .apply(PeriodEvent::Block { height: 200 })
+            .unwrap_err();
+        assert_eq!(
+            err,
+            VoteError::InvalidTransition {
+                period_id: 7,
+                status: PeriodStatus::Cancelled,
+                event: "block",
+            }
+        );
+        assert_eq!(err.code(), "INVALID_TRANSITION");
+    }
+
+    #[test]
+    fn rejects_events_that_go_back() {
+        let mut period = period();
+        period.apply(PeriodEvent::Block { height: 103 }).unwrap();
+        let err = period
+            .apply(PeriodEvent::Block { height: 102 })
+            .unwrap_err();
+        assert_eq!(err.code(), "STALE_EVENT");
+        assert!(period
+            .apply(PeriodEvent::Time {
+                timestamp: START - 1
+            })
+            .is_err());
+
+        // Repeating the latest values is harmless.
+        assert_eq!(
+            period.apply(PeriodEvent::Block { height: 103 }).unwrap(),
+            None
+        );
+        assert_eq!(period.status(), PeriodStatus::Active);
+    }
+
+    #[test]
+    fn failed_votes_leave_period_unchanged() {
+        let mut period = period();
+        period.add_votes(&[vote("1", "a", "10", 1)]).unwrap();
+        assert!(period
+            .add_votes(&[vote("2", "b", "5", 1), vote("3", "c", "1.5", 1)])
+            .is_err());
+
+        let finalized = period.apply(PeriodEvent::Cancel).unwrap().unwrap();
+        assert_eq!(finalized.tally.total_votes, 1);
+        assert_eq!(
+            finalized.votes_merkle_root,
+            votes_merkle_root(&[vote("1", "a", "10", 1).merkle_vote()]).unwrap()
+        );
+    }
+
+    #[test]
+    fn leaves_votes_over_quota_out_of_the_tally() {
+        let mut period = period();
+        period.set_quota_limits(QuotaLimits {
+            cooldown_blocks: 10,
+            ..QuotaLimits::default()
+        });
+        let mut late = vote("3", "a", "7", 1);
+        late.height = Some(110);
+        let rejected = period
+            .add_votes(&[vote("1", "a", "10", 1), vote("2", "a", "5", 0), late])
+            .unwrap();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!((rejected[0].index, rejected[0].voter.as_str()), (1, "a"));
+
+        let usage = period.quota_usage();
+        assert_eq!((usage[0].period_votes, usage[0].last_height), (2, 110));
+
+        // Both admitted votes are in the root; the duplicate policy counts
+        // only the first in the tally.
+        let finalized = period.apply(PeriodEvent::Cancel).unwrap().unwrap();
+        assert_eq!(finalized.tally.total_votes, 1);
+        assert_eq!(finalized.tally.approved, 10);
+        let admitted = [vote("1", "a", "10", 1), vote("3", "a", "7", 1)];
+        let merkle_votes: Vec<MerkleVote> = admitted.iter().map(PeriodVote::merkle_vote).collect();
+        assert_eq!(
+            finalized.votes_merkle_root,
+            votes_merkle_root(&merkle_votes).unwrap()
+        );
+    }
+
+    fn fork(fork_height: u64) -> CompetingChains {
+        CompetingChains {
+            old_chain_id: "old".to_string(),
+            new_chain_id: "new".to_string(),
+            common_ancestor_height: fork_height,
+        }
+    }
+
+    #[test]
+    fn starts_chain_period_from_fork() {
+        let clock = || START;
+        let period =
+            VotingPeriod::for_fork(fork(950), 1_000, &VoteProcessor::new(), 4, &clock).unwrap();
+
+        assert_eq!(period.period_id(), fork(950).period_id());
+        assert_eq!(
+            period.schedule(),
+            PeriodSchedule {
+                start_height: 1_000,
+                end_height: 1_000 + VOTING_PERIOD_BLOCKS,
+                start_time: START,
+                end_time: START + CONSENSUS_TIMEOUT_MS,
+            }
+        );
+        assert_eq!(period.competing_chains(), Some(&fork(950)));
+    }
+
+    #[test]
+    fn period_id_is_derived_from_the_fork() {
+        let id = fork(950).period_id();
+        assert_eq!(id, fork(950).period_id());
+        assert!(id < 1 << 53);
+        assert_ne!(id, fork(951).period_id());
+
+        let mut swapped = fork(950);
+        std::mem::swap(&mut swapped.old_chain_id, &mut swapped.new_chain_id);
+        assert_ne!(id, swapped.period_id());
+    }
+
+    #[test]
+    fn rejects_forks_deeper_than_the_limit() {
+        let clock = || START;
+        let processor = VoteProcessor::new();
+        assert!(VotingPeriod::for_fork(fork(900), 1_000, &processor, 4, &clock).is_ok());
+        assert!(VotingPeriod::for_fork(fork(1_005), 1_000, &processor, 4, &clock).is_ok());
+
+        let err = VotingPeriod::for_fork(fork(500), 1_000, &processor, 4, &clock).unwrap_err();
+        assert_eq!(
+            err,
+            VoteError::ForkTooDeep {
+                fork_height: 500,
+                current_height: 1_000,
+                depth: 500,
+                max: MAX_FORK_DEPTH,
+            }
+        );
+        assert_eq!(err.code(), "FORK_DEPTH_EXCEEDED");
+        assert_eq!(
+            err.to_string(),
+            "Fork depth exceeds maximum allowed: current depth 500 exceeds max allowed 100"
+        );
+    }
+
+    #[test]
+    fn serializes_events_and_status() {
+        let event: PeriodEvent = serde_json::from_str(r#"{"type":"block","height":5}"#).unwrap();
+        assert_eq!(event, PeriodEvent::Block { height: 5 });
+        let event: PeriodEvent = serde_json::from_str(r#"{"type":"cancel"}"#).unwrap();
+        assert_eq!(event, PeriodEvent::Cancel);
+        assert_eq!(
+            serde_json::to_string(&PeriodStatus::Completed).unwrap(),
+            r#""completed""#
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Quadratic voting power.
+//!
+//! This is the canonical version of the calculation that
+//! `UtxoSet.calculateVotingPower`, `DirectVoting.processVote` and `peer.ts`
+//! each approximate in TypeScript: power is the floor of the integer square
+//! root of the balance, bounded by `VOTING_CONSTANTS.MIN_VOTING_POWER` and
+//! `VOTING_CONSTANTS.MAX_VOTING_POWER`.
+
+use crate::error::VoteError;
+use std::str::FromStr;
+
+/// `VOTING_CONSTANTS.MIN_VOTING_POWER`.
+pub const MIN_VOTING_POWER: u128 = 100;
+/// `VOTING_CONSTANTS.MAX_VOTING_POWER`.
+pub const MAX_VOTING_POWER: u128 = 1_000_000;
+
+/// How a vote's balance is turned into weight in a tally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TallyMode {
+    /// Each vote weighs its raw balance.
+    #[default]
+    Balance,
+    /// Each vote weighs [`quadratic_power`] of its balance.
+    Quadratic,
+}
+
+impl TallyMode {
+    pub fn weight(self, balance: u128) -> u128 {
+        match self {
+            TallyMode::Balance => balance,
+            TallyMode::Quadratic => quadratic_power(balance),
+        }
+    }
+}
+
+/// Parses the names used by the JS bindings: `"balance"` or `"quadratic"`.
+impl FromStr for TallyMode {
+    type Err = VoteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "balance" => Ok(TallyMode::Balance),
+            "quadratic" => Ok(TallyMode::Quadratic),
+            other => Err(VoteError::InvalidArgument(format!(
+                "Unknown tally mode: {other}"
+            ))),
+        }
+    }
+}
+
+/// Floor of the square root of `value`, using the same Newton iteration as
+/// `UtxoSet.bigIntSqrt`.
+pub fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+
+    let mut x0 = value / 2;
+    let mut x1 = (x0 + value / x0) / 2;
+    while x1 < x0 {
+        x0 = x1;
+        x1 = (x0 + value / x0) / 2;
+    }
+    x0
+}
+
+/// Quadratic voting power of `balance`.
+///
+/// Balances whose square root is below [`MIN_VOTING_POWER`] carry no power,
+/// and power is capped at [`MAX_VOTING_POWER`].
+pub fn quadratic_power(balance: u128) -> u128 {
+    let power = isqrt(balance);
+    if power < MIN_VOTING_POWER {
+        0
+    } else {
+        power.min(MAX_VOTING_POWER)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mode_names() {
+        assert_eq!("balance".parse::<TallyMode>(), Ok(TallyMode::Balance));
+        assert_eq!("quadratic".parse::<TallyMode>(), Ok(TallyMode::Quadratic));
+        assert_eq!(
+            "Quadratic".parse::<TallyMode>().unwrap_err().code(),
+            "INVALID_ARGUMENT"
+        );
+    }
+
+    #[test]
+    fn isqrt_is_exact_floor() {
+        for value in [0u128, 1, 2, 3, 4, 15, 16, 17, 99, 100, 1 << 64] {
+            let root = isqrt(value);
+            assert!(root * root <= value, "{value}");
+            assert!((root + 1) * (root + 1) > value, "{value}");
+        }
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+    }
+
+    #[test]
+    fn quadratic_power_applies_bounds() {
+        assert_eq!(quadratic_power(0), 0);
+        assert_eq!(quadratic_power(9_999), 0);
+        assert_eq!(quadratic_power(10_000), 100);
+        assert_eq!(quadratic_power(10_200), 100);
+        assert_eq!(quadratic_power(250_000), 500);
+        assert_eq!(quadratic_power(1_000_000_000_000), MAX_VOTING_POWER);
+        assert_eq!(quadratic_power(u128::MAX), MAX_VOTING_POWER);
+    }
+}
@@ -0,0 +1,584 @@
+use crate::accumulator::VoteTallyAccumulator;
+use crate::amount::Amount;
+use crate::decode::decode_votes;
+use crate::details::{voter_details, VoterDetail};
+use crate::duplicates::{counted_mask, find_duplicates, DuplicatePolicy, DuplicateVoter};
+use crate::error::VoteError;
+use crate::power::TallyMode;
+use crate::tally::VoteTally;
+use crate::weighting::Weighting;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Default number of votes handled per chunk.
+pub const DEFAULT_CHUNK_SIZE: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoteData {
+    pub balance: String,
+    pub approved: i32,
+    pub voter: String,
+    /// Vote submission time in milliseconds. Only used to resolve duplicates
+    /// under [`DuplicatePolicy::LastWins`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkResult {
+    /// Sum of approving weight (see [`TallyMode`]). Serialized to JS as a `BigInt`.
+    pub approved: u128,
+    /// Sum of rejecting weight (see [`TallyMode`]). Serialized to JS as a `BigInt`.
+    pub rejected: u128,
+    /// Distinct voters, sorted by byte order so results compare equal
+    /// across runs and nodes.
+    pub voters: Vec<String>,
+    /// Voters with more than one vote in the chunk, whatever the policy.
+    pub duplicates: Vec<DuplicateVoter>,
+    /// One entry per voter, in the order of `voters`. Only present in
+    /// detailed mode (see [`VoteProcessor::set_detailed`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Vec<VoterDetail>>,
+}
+
+/// A vote's parsed balance and the weight it adds to each side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct VoteWeight {
+    pub balance: u128,
+    pub approved: u128,
+    pub rejected: u128,
+}
+
+/// Pure-Rust vote processor. Holds no wasm-bindgen types so it can be used
+/// and tested natively; `WasmVoteProcessor` is a thin wrapper around it.
+#[derive(Debug, Clone)]
+pub struct VoteProcessor {
+    chunk_size: usize,
+    tally_mode: TallyMode,
+    duplicate_policy: DuplicatePolicy,
+    detailed: bool,
+    weighting: Option<Weighting>,
+}
+
+impl Default for VoteProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VoteProcessor {
+    pub fn new() -> Self {
+        VoteProcessor {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            tally_mode: TallyMode::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            detailed: false,
+            weighting: None,
+        }
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.chunk_size = chunk_size;
+    }
+
+    pub fn tally_mode(&self) -> TallyMode {
+        self.tally_mode
+    }
+
+    pub fn set_tally_mode(&mut self, mode: TallyMode) {
+        self.tally_mode = mode;
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    pub fn detailed(&self) -> bool {
+        self.detailed
+    }
+
+    /// Whether results include a [`VoterDetail`] for every voter. Off by
+    /// default, as it costs a pass over the chunk and a larger result.
+    pub fn set_detailed(&mut self, detailed: bool) {
+        self.detailed = detailed;
+    }
+
+    pub fn weighting(&self) -> Option<&Weighting> {
+        self.weighting.as_ref()
+    }
+
+    /// Applies age decay and maturity rules to every vote's power after the
+    /// [`TallyMode`], or stops applying them with `None`. Votes from
+    /// immature voters still count as cast but add no power.
+    pub fn set_weighting(&mut self, weighting: Option<Weighting>) {
+        self.weighting = weighting;
+    }
+
+    /// Tallies a chunk of votes. A vote with `approved > 0` counts towards
+    /// `approved`, anything else towards `rejected`, weighted according to
+    /// the processor's [`TallyMode`]. Voters with several votes are handled
+    /// according to the processor's [`DuplicatePolicy`].
+    ///
+    /// Sums are exact integers with checked overflow, so the result does not
+    /// depend on how rayon splits the work.
+    pub fn process_chunk(&self, votes: &[VoteData]) -> Result<ChunkResult, VoteError> {
+        self.check_chunk_size(votes.len())?;
+        let duplicates = find_duplicates(votes);
+        let counted = counted_mask(votes, &duplicates, self.duplicate_policy)?;
+
+        // Parse and weight in parallel; the first failing vote is reported.
+        let weights = try_map_in_order(votes, |index, vote| self.vote_weight(index, vote))?;
+
+        // Summing in order lets an overflow name the vote that caused it.
+        let mut approved: u128 = 0;
+        let mut rejected: u128 = 0;
+        for (index, ((vote, weight), _)) in votes
+            .iter()
+            .zip(&weights)
+            .zip(&counted)
+            .enumerate()
+            .filter(|(_, (_, &counted))| counted)
+        {
+            approved = approved
+                .checked_add(weight.approved)
+                .ok_or_else(|| overflow("approved", index, vote))?;
+            rejected = rejected
+                .checked_add(weight.rejected)
+                .ok_or_else(|| overflow("rejected", index, vote))?;
+        }
+
+        let unique_voters: BTreeSet<&str> = votes.iter().map(|vote| vote.voter.as_str()).collect();
+
+        Ok(ChunkResult {
+            approved,
+            rejected,
+            voters: unique_voters.into_iter().map(String::from).collect(),
+            duplicates,
+            details: self
+                .detailed
+                .then(|| voter_details(votes, &weights, &counted)),
+        })
+    }
+
+    /// Tallies a chunk into the consensus layer's full [`VoteTally`], with
+    /// participation measured against `eligible_voters`.
+    pub fn tally_votes(
+        &self,
+        votes: &[VoteData],
+        eligible_voters: u64,
+        timestamp: u64,
+    ) -> Result<VoteTally, VoteError> {
+        let result = self.process_chunk(votes)?;
+        Ok(VoteTally::from_result(
+            &result,
+            self.duplicate_policy,
+            votes.len(),
+            eligible_voters,
+            timestamp,
+        ))
+    }
+
+    /// Decodes votes in the `serializeVotes` binary layout and tallies them.
+    pub fn process_bytes(&self, bytes: &[u8]) -> Result<ChunkResult, VoteError> {
+        let votes = decode_votes(bytes)?;
+        self.process_chunk(&votes)
+    }
+
+    /// Starts a [`VoteTallyAccumulator`] that tallies chunks with this
+    /// processor's settings.
+    pub fn accumulator(&self) -> VoteTallyAccumulator {
+        VoteTallyAccumulator::new(self.clone())
+    }
+
+    pub(crate) fn check_chunk_size(&self, len: usize) -> Result<(), VoteError> {
+        if len > self.chunk_size {
+            return Err(VoteError::ChunkTooLarge {
+                len,
+                max: self.chunk_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Parses the balance of vote `index` and weights it towards the side
+    /// it votes for.
+    pub(crate) fn vote_weight(
+        &self,
+        index: usize,
+        vote: &VoteData,
+    ) -> Result<VoteWeight, VoteError> {
+        let balance = parse_balance(index, &vote.balance, &vote.voter)?;
+        let weight = self.power(balance, &vote.voter, vote.timestamp);
+        let (approved, rejected) = if vote.approved > 0 {
+            (weight, 0)
+        } else {
+            (0, weight)
+        };
+        Ok(VoteWeight {
+            balance,
+            approved,
+            rejected,
+        })
+    }
+
+    /// Parses the balance of vote `index` and weights it according to the
+    /// [`TallyMode`] and any [`Weighting`].
+    pub(crate) fn balance_weight(
+        &self,
+        index: usize,
+        balance: &str,
+        voter: &str,
+        timestamp: Option<u64>,
+    ) -> Result<u128, VoteError> {
+        let balance = parse_balance(index, balance, voter)?;
+        Ok(self.power(balance, voter, timestamp))
+    }
+
+    fn power(&self, balance: u128, voter: &str, timestamp: Option<u64>) -> u128 {
+        let power = self.tally_mode.weight(balance);
+        match &self.weighting {
+            Some(weighting) => weighting.apply(power, voter, timestamp),
+            None => power,
+        }
+    }
+}
+
+fn parse_balance(index: usize, balance: &str, voter: &str) -> Result<u128, VoteError> {
+    let amount = balance
+        .parse::<Amount>()
+        .map_err(|reason| VoteError::InvalidAmount {
+            index,
+            voter: voter.to_string(),
+            value: balance.to_string(),
+            reason,
+        })?;
+    Ok(amount.get())
+}
+
+/// Maps `f` over `items` in parallel and fails with the error of the lowest
+/// failing index. Collecting straight into a `Result` would report whichever
+/// error a thread reached first, which varies between runs and nodes.
+pub(crate) fn try_map_in_order<T, R, F>(items: &[T], f: F) -> Result<Vec<R>, VoteError>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &T) -> Result<R, VoteError> + Sync + Send,
+{
+    let results: Vec<Result<R, VoteError>> = items
+        .par_iter()
+        .enumerate()
+        .map(|(index, item)| f(index, item))
+        .collect();
+    results.into_iter().collect()
+}
+
+fn overflow(tally: &str, index: usize, vote: &VoteData) -> VoteError {
+    VoteError::Overflow {
+        tally: tally.to_string(),
+        index: Some(index),
+        voter: Some(vote.voter.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::AmountError;
+
+    fn vote(balance: &str, approved: i32, voter: &str) -> VoteData {
+        VoteData {
+            balance: balance.to_string(),
+            approved,
+            voter: voter.to_string(),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn tallies_approved_and_rejected() {
+        let votes = vec![vote("100", 1, "a"), vote("40", 0, "b"), vote("60", 1, "c")];
+        let result = VoteProcessor::new().process_chunk(&votes).unwrap();
+
+        assert_eq!(result.approved, 160);
+        assert_eq!(result.rejected, 40);
+        assert_eq!(result.voters, vec!["a", "b", "c"]);
+        assert_eq!(result.details, None);
+    }
+
+    #[test]
+    fn weighting_applies_after_tally_mode() {
+        use crate::weighting::{AgeRules, DecayCurve, VoterAge, Weighting};
+
+        let mature = |voter: &str| VoterAge {
+            voter: voter.to_string(),
+            account_age: Some(20_000),
+            coin_age: None,
+        };
+        let rules = AgeRules {
+            curve: DecayCurve::Linear { zero_at_ms: 100 },
+            ..AgeRules::default()
+        };
+        let mut votes = vec![
+            vote("10000", 1, "a"),
+            vote("40000", 0, "b"),
+            vote("90000", 1, "c"),
+        ];
+        votes[0].timestamp = Some(975);
+        let mut processor = VoteProcessor::new();
+        processor.set_tally_mode(TallyMode::Quadratic);
+        processor.set_weighting(Some(
+            Weighting::new(rules, 1_000, [mature("a"), mature("b")]).unwrap(),
+        ));
+
+        let result = processor.process_chunk(&votes).unwrap();
+        assert_eq!((result.approved, result.rejected), (75, 200));
+        assert_eq!(result.voters, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn sorts_voters() {
+        let votes = vec![
+            vote("1", 1, "c"),
+            vote("1", 1, "B"),
+            vote("1", 1, "a"),
+            vote("1", 0, "c"),
+        ];
+        let result = VoteProcessor::new().process_chunk(&votes).unwrap();
+        assert_eq!(result.voters, vec!["B", "a", "c"]);
+    }
+
+    #[test]
+    fn details_counted_votes_per_voter() {
+        use crate::details::VoterChoice;
+
+        let votes = vec![
+            vote("40000", 1, "b"),
+            vote("10000", 0, "a"),
+            vote("90000", 1, "b"),
+            vote("50", 1, "dust"),
+        ];
+        let mut processor = VoteProcessor::new();
+        processor.set_tally_mode(TallyMode::Quadratic);
+        processor.set_duplicate_policy(DuplicatePolicy::Sum);
+        processor.set_detailed(true);
+
+        let detail = |voter: &str, vote_count, balance, power, choice| VoterDetail {
+            voter: voter.to_string(),
+            vote_count,
+            balance,
+            power,
+            choice,
+        };
+        let result = processor.process_chunk(&votes).unwrap();
+        assert_eq!(
+            result.details.unwrap(),
+            vec![
+                detail("a", 1, 10_000, 100, VoterChoice::Reject),
+                detail("b", 2, 130_000, 500, VoterChoice::Approve),
+                detail("dust", 1, 50, 0, VoterChoice::Approve),
+            ]
+        );
+
+        processor.set_duplicate_policy(DuplicatePolicy::FirstWins);
+        let result = processor.process_chunk(&votes).unwrap();
+        assert_eq!(
+            result.details.unwrap()[1],
+            detail("b", 2, 40_000, 200, VoterChoice::Approve)
+        );
+    }
+
+    #[test]
+    fn empty_chunk_is_zero() {
+        let result = VoteProcessor::new().process_chunk(&[]).unwrap();
+        assert_eq!(result.approved, 0);
+        assert_eq!(result.rejected, 0);
+        assert!(result.voters.is_empty());
+    }
+
+    #[test]
+    fn reports_unparseable_balance() {
+        let votes = vec![vote("100", 1, "a"), vote("abc", 1, "bad")];
+        let err = VoteProcessor::new().process_chunk(&votes).unwrap_err();
+        assert_eq!(
+            err,
+            VoteError::InvalidAmount {
+                index: 1,
+                voter: "bad".to_string(),
+                value: "abc".to_string(),
+                reason: AmountError::InvalidDigit,
+            }
+        );
+        assert_eq!(err.code(), "PARSE_ERROR");
+    }
+
+    #[test]
+    fn sums_exactly_above_f64_precision() {
+        // 10^16 + 1 is above 2^53 and not representable as f64.
+        let votes = vec![
+            vote("4000000000000001", 1, "a"),
+            vote("4000000000000000", 1, "b"),
+            vote("2000000000000000", 1, "c"),
+        ];
+        let result = VoteProcessor::new().process_chunk(&votes).unwrap();
+        assert_eq!(result.approved, 10_000_000_000_000_001);
+    }
+
+    #[test]
+    fn rejects_non_integer_balances() {
+        for (balance, reason) in [
+            ("1.5", AmountError::InvalidDigit),
+            ("-5", AmountError::Negative),
+            ("inf", AmountError::InvalidDigit),
+            ("NaN", AmountError::InvalidDigit),
+            ("1e300", AmountError::InvalidDigit),
+            ("042", AmountError::LeadingZero),
+            ("", AmountError::Empty),
+        ] {
+            let votes = vec![vote(balance, 1, "a")];
+            match VoteProcessor::new().process_chunk(&votes) {
+                Err(VoteError::InvalidAmount { reason: got, .. }) => {
+                    assert_eq!(got, reason, "{balance}")
+                }
+                other => panic!("{balance}: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn reports_the_lowest_failing_index() {
+        // Large enough for rayon to split the chunk across threads.
+        let mut votes: Vec<VoteData> = (0..20_000)
+            .map(|i| vote("1", 1, &format!("v{i}")))
+            .collect();
+        votes[19_000].balance = "bad".to_string();
+        votes[7].balance = "-1".to_string();
+        for _ in 0..5 {
+            let err = VoteProcessor::new().process_chunk(&votes).unwrap_err();
+            assert_eq!((err.index(), err.voter()), (Some(7), Some("v7")));
+        }
+    }
+
+    #[test]
+    fn accepts_hex_balances() {
+        let votes = vec![vote("0x64", 1, "a"), vote("10", 0, "b")];
+        let result = VoteProcessor::new().process_chunk(&votes).unwrap();
+        assert_eq!((result.approved, result.rejected), (100, 10));
+    }
+
+    #[test]
+    fn rejects_balances_above_supply() {
+        let votes = vec![vote("1", 0, "a"), vote(&u128::MAX.to_string(), 0, "b")];
+        let err = VoteProcessor::new().process_chunk(&votes).unwrap_err();
+        assert_eq!(
+            (err.code(), err.index(), err.voter()),
+            ("AMOUNT_OUT_OF_RANGE", Some(1), Some("b"))
+        );
+    }
+
+    #[test]
+    fn quadratic_mode_weights_by_power() {
+        let votes = vec![
+            vote("1000000", 1, "whale"),
+            vote("40000", 0, "a"),
+            vote("90000", 0, "b"),
+            vote("50", 0, "dust"),
+        ];
+        let mut processor = VoteProcessor::new();
+        processor.set_tally_mode(TallyMode::Quadratic);
+
+        let result = processor.process_chunk(&votes).unwrap();
+        assert_eq!(result.approved, 1_000);
+        assert_eq!(result.rejected, 200 + 300);
+    }
+
+    #[test]
+    fn applies_duplicate_policy() {
+        let mut votes = vec![vote("10", 1, "a"), vote("5", 0, "b"), vote("7", 0, "a")];
+        votes[0].timestamp = Some(2);
+        votes[2].timestamp = Some(1);
+        let mut processor = VoteProcessor::new();
+
+        // First wins unless a policy is chosen.
+        let result = processor.process_chunk(&votes).unwrap();
+        assert_eq!((result.approved, result.rejected), (10, 5));
+        assert_eq!(result.duplicates[0].indices, vec![0, 2]);
+
+        processor.set_duplicate_policy(DuplicatePolicy::Sum);
+        let result = processor.process_chunk(&votes).unwrap();
+        assert_eq!((result.approved, result.rejected), (10, 12));
+
+        processor.set_duplicate_policy(DuplicatePolicy::LastWins);
+        let result = processor.process_chunk(&votes).unwrap();
+        assert_eq!((result.approved, result.rejected), (10, 5));
+        assert_eq!(result.duplicates.len(), 1);
+
+        processor.set_duplicate_policy(DuplicatePolicy::Reject);
+        let err = processor.process_chunk(&votes).unwrap_err();
+        assert_eq!(
+            (err.code(), err.index(), err.voter()),
+            ("DUPLICATE_VOTE", Some(2), Some("a"))
+        );
+    }
+
+    #[test]
+    fn tally_votes_fills_vote_tally() {
+        let votes = vec![vote("10", 1, "a"), vote("5", 0, "b"), vote("7", 0, "a")];
+        let mut processor = VoteProcessor::new();
+        processor.set_duplicate_policy(DuplicatePolicy::FirstWins);
+
+        let tally = processor.tally_votes(&votes, 4, 1_700_000_000_000).unwrap();
+        assert_eq!(
+            tally,
+            VoteTally {
+                approved: 10,
+                rejected: 5,
+                total_votes: 2,
+                unique_voters: 2,
+                participation_rate: 0.5,
+                timestamp: 1_700_000_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn enforces_chunk_size() {
+        let mut processor = VoteProcessor::new();
+        processor.set_chunk_size(1);
+        let err = processor
+            .process_chunk(&[vote("1", 1, "a"), vote("1", 1, "b")])
+            .unwrap_err();
+        assert_eq!(err, VoteError::ChunkTooLarge { len: 2, max: 1 });
+    }
+
+    #[test]
+    fn tallies_binary_layout() {
+        let mut bytes = Vec::new();
+        for (balance, approved, voter) in [(70i64, 1i32, "a"), (30, 0, "b")] {
+            bytes.extend_from_slice(&balance.to_le_bytes());
+            bytes.extend_from_slice(&approved.to_le_bytes());
+            bytes.extend_from_slice(&(voter.len() as i32).to_le_bytes());
+            bytes.extend_from_slice(voter.as_bytes());
+        }
+
+        let result = VoteProcessor::new().process_bytes(&bytes).unwrap();
+        assert_eq!((result.approved, result.rejected), (70, 30));
+
+        let err = VoteProcessor::new()
+            .process_bytes(&bytes[..20])
+            .unwrap_err();
+        assert_eq!((err.code(), err.index()), ("DECODE_ERROR", Some(1)));
+        assert!(
+            err.to_string().contains("vote 1 at byte offset 17"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Per-voter vote quotas within a voting period, from `VOTING_CONSTANTS`.
+//!
+//! `DDoSProtection` rate-limits votes per node, so two nodes can admit
+//! different votes. Here the limits are applied to the votes themselves:
+//! windows are fixed spans of vote timestamps and cooldowns are measured in
+//! vote heights, so every node admitting the same votes in the same order
+//! rejects the same ones.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// `VOTING_CONSTANTS.MAX_VOTES_PER_WINDOW`.
+pub const MAX_VOTES_PER_WINDOW: u32 = 5;
+/// `VOTING_CONSTANTS.MAX_VOTES_PER_PERIOD`.
+pub const MAX_VOTES_PER_PERIOD: u32 = 100_000;
+/// `VOTING_CONSTANTS.RATE_LIMIT_WINDOW` (3600 seconds) in milliseconds.
+pub const RATE_LIMIT_WINDOW_MS: u64 = 3_600_000;
+/// `VOTING_CONSTANTS.COOLDOWN_BLOCKS`.
+pub const COOLDOWN_BLOCKS: u64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaLimits {
+    pub max_votes_per_window: u32,
+    pub max_votes_per_period: u32,
+    /// Windows start at multiples of this many milliseconds.
+    pub window_ms: u64,
+    /// Blocks a voter must wait after an admitted vote.
+    pub cooldown_blocks: u64,
+}
+
+impl Default for QuotaLimits {
+    fn default() -> Self {
+        QuotaLimits {
+            max_votes_per_window: MAX_VOTES_PER_WINDOW,
+            max_votes_per_period: MAX_VOTES_PER_PERIOD,
+            window_ms: RATE_LIMIT_WINDOW_MS,
+            cooldown_blocks: COOLDOWN_BLOCKS,
+        }
+    }
+}
+
+impl QuotaLimits {
+    fn window_start(&self, timestamp: u64) -> u64 {
+        match self.window_ms {
+            0 => 0,
+            window_ms => timestamp - timestamp % window_ms,
+        }
+    }
+}
+
+/// Why a vote was over quota. Serialized as `{ "reason": "...", ... }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "reason",
+    rename_all = "kebab-case",
+    rename_all_fields = "camelCase"
+)]
+pub enum QuotaViolation {
+    /// The voter already has `limit` admitted votes in the period.
+    PeriodQuotaExceeded { limit: u32 },
+    /// The voter already has `limit` admitted votes in the window starting
+    /// at `window_start`.
+    WindowQuotaExceeded { limit: u32, window_start: u64 },
+    /// The voter's last admitted vote was at `last_height`; the vote is
+    /// `remaining` blocks too early.
+    Cooldown { last_height: u64, remaining: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaRejection {
+    /// Index of the vote in its chunk.
+    pub index: usize,
+    pub voter: String,
+    #[serde(flatten)]
+    pub violation: QuotaViolation,
+}
+
+/// A voter's admitted votes so far.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaUsage {
+    pub voter: String,
+    pub period_votes: u32,
+    /// Start of the latest window the voter has votes in.
+    pub window_start: u64,
+    pub window_votes: u32,
+    pub last_height: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct VoterQuota {
+    period_votes: u32,
+    /// Admitted votes by window start.
+    windows: BTreeMap<u64, u32>,
+    last_height: Option<u64>,
+}
+
+/// One vote to admit: voter, timestamp in milliseconds and block height.
+pub(crate) type QuotaVote<'a> = (&'a str, u64, u64);
+
+/// The outcome of [`VoteQuotas::check`], applied with [`VoteQuotas::commit`].
+#[derive(Debug)]
+pub(crate) struct QuotaPass {
+    /// Per vote, whether it was admitted.
+    pub admitted: Vec<bool>,
+    pub rejected: Vec<QuotaRejection>,
+    updates: HashMap<String, VoterQuota>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VoteQuotas {
+    limits: QuotaLimits,
+    voters: HashMap<String, VoterQuota>,
+}
+
+impl VoteQuotas {
+    pub fn new(limits: QuotaLimits) -> Self {
+        VoteQuotas {
+            limits,
+            voters: HashMap::new(),
+        }
+    }
+
+    pub fn limits(&self) -> QuotaLimits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: QuotaLimits) {
+        self.limits = limits;
+    }
+
+    /// Admits votes in order, each counting against the quotas of the votes
+    /// after it. Nothing is recorded until the pass is committed, so a chunk
+    /// that fails later can be dropped.
+    ///
+    /// Checks run in this order: period quota, cooldown, window quota.
+    pub(crate) fn check(&self, votes: &[QuotaVote<'_>]) -> QuotaPass {
+        let mut pass = QuotaPass {
+            admitted: Vec::with_capacity(votes.len()),
+            rejected: Vec::new(),
+            updates: HashMap::new(),
+        };
+        for (index, &(voter, timestamp, height)) in votes.iter().enumerate() {
+            let quota = pass
+                .updates
+                .entry(voter.to_string())
+                .or_insert_with(|| self.voters.get(voter).cloned().unwrap_or_default());
+            match self.admit(quota, timestamp, height) {
+                Ok(()) => pass.admitted.push(true),
+                Err(violation) => {
+                    pass.admitted.push(false);
+                    pass.rejected.push(QuotaRejection {
+                        index,
+                        voter: voter.to_string(),
+                        violation,
+                    });
+                }
+            }
+        }
+        pass
+    }
+
+    pub(crate) fn commit(&mut self, pass: QuotaPass) {
+        self.voters.extend(pass.updates);
+    }
+
+    fn admit(
+        &self,
+        quota: &mut VoterQuota,
+        timestamp: u64,
+        height: u64,
+    ) -> Result<(), QuotaViolation> {
+        let limits = &self.limits;
+        if quota.period_votes >= limits.max_votes_per_period {
+            return Err(QuotaViolation::PeriodQuotaExceeded {
+                limit: limits.max_votes_per_period,
+            });
+        }
+        if let Some(last_height) = quota.last_height {
+            let ready_at = last_height.saturating_add(limits.cooldown_blocks);
+            if height < ready_at {
+                return Err(QuotaViolation::Cooldown {
+                    last_height,
+                    remaining: ready_at - height,
+                });
+            }
+        }
+        let window_start = limits.window_start(timestamp);
+        let window_votes = quota.windows.entry(window_start).or_default();
+        if *window_votes >= limits.max_votes_per_window {
+            return Err(QuotaViolation::WindowQuotaExceeded {
+                limit: limits.max_votes_per_window,
+                window_start,
+            });
+        }
+
+        *window_votes += 1;
+        quota.period_votes += 1;
+        quota.last_height = Some(quota.last_height.map_or(height, |last| last.max(height)));
+        Ok(())
+    }
+
+    /// Usage for every voter with an admitted vote, sorted by voter.
+    pub fn usage(&self) -> Vec<QuotaUsage> {
+        let mut usage: Vec<QuotaUsage> = self
+            .voters
+            .iter()
+            .filter_map(|(voter, quota)| {
+                let (&window_start, &window_votes) =
+                    quota.windows.iter().rev().find(|(_, &votes)| votes > 0)?;
+                Some(QuotaUsage {
+                    voter: voter.clone(),
+                    period_votes: quota.period_votes,
+                    window_start,
+                    window_votes,
+                    last_height: quota.last_height.unwrap_or_default(),
+                })
+            })
+            .collect();
+        usage.sort_by(|a, b| a.voter.cmp(&b.voter));
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: u64 = RATE_LIMIT_WINDOW_MS;
+
+    fn limits(per_window: u32, per_period: u32, cooldown_blocks: u64) -> QuotaLimits {
+        QuotaLimits {
+            max_votes_per_window: per_window,
+            max_votes_per_period: per_period,
+            window_ms: HOUR,
+            cooldown_blocks,
+        }
+    }
+
+    fn admit(quotas: &mut VoteQuotas, votes: &[QuotaVote<'_>]) -> Vec<QuotaRejection> {
+        let pass = quotas.check(votes);
+        let rejected = pass.rejected.clone();
+        quotas.commit(pass);
+        rejected
+    }
+
+    #[test]
+    fn default_limits_match_voting_constants() {
+        let limits = QuotaLimits::default();
+        assert_eq!(limits.max_votes_per_window, 5);
+        assert_eq!(limits.max_votes_per_period, 100_000);
+        assert_eq!(limits.window_ms, 3_600_000);
+        assert_eq!(limits.cooldown_blocks, 100);
+    }
+
+    #[test]
+    fn limits_votes_per_window_across_chunks() {
+        let mut quotas = VoteQuotas::new(limits(2, 100, 0));
+        assert!(admit(&mut quotas, &[("a", 10, 1), ("b", 20, 1)]).is_empty());
+        let rejected = admit(
+            &mut quotas,
+            &[("a", 30, 2), ("a", HOUR - 1, 3), ("a", HOUR, 4)],
+        );
+
+        assert_eq!(
+            rejected,
+            vec![QuotaRejection {
+                index: 1,
+                voter: "a".to_string(),
+                violation: QuotaViolation::WindowQuotaExceeded {
+                    limit: 2,
+                    window_start: 0,
+                },
+            }]
+        );
+        let usage = quotas.usage();
+        assert_eq!(usage[0].voter, "a");
+        assert_eq!(
+            (
+                usage[0].period_votes,
+                usage[0].window_start,
+                usage[0].window_votes
+            ),
+            (3, HOUR, 1)
+        );
+        assert_eq!(usage[1].period_votes, 1);
+    }
+
+    #[test]
+    fn enforces_cooldown_and_period_quota() {
+        let mut quotas = VoteQuotas::new(limits(10, 2, 100));
+        let rejected = admit(
+            &mut quotas,
+            &[
+                ("a", 1, 1_000),
+                ("a", 2, 1_050),
+                ("a", 3, 1_100),
+                ("a", 4 * HOUR, 5_000),
+            ],
+        );
+
+        assert_eq!(
+            rejected
+                .iter()
+                .map(|r| r.violation.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                QuotaViolation::Cooldown {
+                    last_height: 1_000,
+                    remaining: 50,
+                },
+                QuotaViolation::PeriodQuotaExceeded { limit: 2 },
+            ]
+        );
+        assert_eq!(quotas.usage()[0].last_height, 1_100);
+    }
+
+    #[test]
+    fn uncommitted_pass_changes_nothing() {
+        let mut quotas = VoteQuotas::new(limits(1, 100, 0));
+        let pass = quotas.check(&[("a", 1, 1)]);
+        assert_eq!(pass.admitted, vec![true]);
+        assert!(quotas.usage().is_empty());
+        assert!(admit(&mut quotas, &[("a", 1, 1)]).is_empty());
+    }
+
+    #[test]
+    fn serializes_rejections() {
+        let rejection = QuotaRejection {
+            index: 3,
+            voter: "a".to_string(),
+            violation: QuotaViolation::Cooldown {
+                last_height: 10,
+                remaining: 90,
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&rejection).unwrap(),
+            r#"{"index":3,"voter":"a","reason":"cooldown","lastHeight":10,"remaining":90}"#
+        );
+    }
+}
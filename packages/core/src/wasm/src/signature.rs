@@ -0,0 +1,204 @@
+//! Batch verification of vote signatures.
+//!
+//! `DirectVotingUtil._verifyVote` checks one vote at a time against the
+//! message `"${targetChainId}:${timestamp}"`. This module verifies a whole
+//! chunk in parallel against the same message. `vote.signature` is the
+//! JSON string produced by `HybridCrypto.sign`: `{ ecc, dilithium, kyber }`,
+//! where `ecc` is a DER-encoded secp256k1 ECDSA signature (hex) over the
+//! SHA-256 digest of the message, checked against the vote's hex
+//! `publicKey` as `HybridCrypto.verify` does.
+//!
+//! `dilithium` and `kyber` must be present, as in `HybridCrypto.verify`, but
+//! are not checked. `dilithium` is `Dilithium.hash(message)`, a `Buffer`
+//! serialized as `{ type, data }` by a native hash with no public key
+//! involved, and `kyber` is the shared secret of a randomized
+//! encapsulation; neither can be reproduced by a verifier.
+
+use k256::ecdsa::signature::hazmat::PrehashVerifier;
+use k256::ecdsa::{Signature, VerifyingKey};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The signed fields of a `Vote` together with its public key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedVote {
+    pub voter: String,
+    pub target_chain_id: String,
+    pub timestamp: u64,
+    /// JSON-encoded hybrid signature.
+    pub signature: String,
+    /// secp256k1 public key, SEC1-encoded hex.
+    pub public_key: String,
+}
+
+impl SignedVote {
+    pub fn message(&self) -> String {
+        vote_message(&self.target_chain_id, self.timestamp)
+    }
+}
+
+/// The message a voter signs, as built by `_verifyVote`.
+pub fn vote_message(target_chain_id: &str, timestamp: u64) -> String {
+    format!("{target_chain_id}:{timestamp}")
+}
+
+#[derive(Deserialize)]
+struct HybridSignature {
+    ecc: String,
+    #[serde(default)]
+    dilithium: serde_json::Value,
+    #[serde(default)]
+    kyber: serde_json::Value,
+}
+
+/// Whether `HybridCrypto.verify`'s `!component` check would pass.
+fn is_truthy(value: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(number) => number.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(string) => !string.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+/// Verifies a `HybridCrypto.sign` signature over `message`. Any malformed
+/// or missing component makes the signature invalid.
+pub fn verify_hybrid(message: &str, signature: &str, public_key: &str) -> bool {
+    let Ok(signature) = serde_json::from_str::<HybridSignature>(signature) else {
+        return false;
+    };
+    if signature.ecc.is_empty() || !is_truthy(&signature.dilithium) || !is_truthy(&signature.kyber)
+    {
+        return false;
+    }
+
+    verify_ecc(message, &signature.ecc, public_key)
+}
+
+fn verify_ecc(message: &str, signature_hex: &str, public_key_hex: &str) -> bool {
+    let (Ok(signature), Ok(public_key)) = (hex::decode(signature_hex), hex::decode(public_key_hex))
+    else {
+        return false;
+    };
+    let (Ok(signature), Ok(key)) = (
+        Signature::from_der(&signature),
+        VerifyingKey::from_sec1_bytes(&public_key),
+    ) else {
+        return false;
+    };
+    // `elliptic` signs the digest as a number and accepts high-S signatures,
+    // which it does not normalize, so normalize before verifying.
+    let signature = signature.normalize_s().unwrap_or(signature);
+    let digest = Sha256::digest(message.as_bytes());
+    key.verify_prehash(&digest, &signature).is_ok()
+}
+
+/// Verifies every vote's signature in parallel. Entry `i` is `true` when
+/// vote `i` is validly signed.
+pub fn verify_votes(votes: &[SignedVote]) -> Vec<bool> {
+    votes
+        .par_iter()
+        .map(|vote| verify_hybrid(&vote.message(), &vote.signature, &vote.public_key))
+        .collect()
+}
+
+/// Packs per-vote results into a bitmap: bit `i % 8` of byte `i / 8` is set
+/// when vote `i` is valid.
+pub fn to_bitmap(valid: &[bool]) -> Vec<u8> {
+    let mut bitmap = vec![0u8; valid.len().div_ceil(8)];
+    for (index, _) in valid.iter().enumerate().filter(|(_, &valid)| valid) {
+        bitmap[index / 8] |= 1 << (index % 8);
+    }
+    bitmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::signature::hazmat::PrehashSigner;
+    use k256::ecdsa::SigningKey;
+
+    /// `JSON.stringify` of a `HybridCrypto.sign` result for
+    /// `"chain-a:1700000000000"` with the private key `07` * 32, with the
+    /// `Buffer` from `Dilithium.hash` shortened.
+    const TS_SIGNATURE: &str = r#"{"ecc":"3044022078e248ba160ed38c49e2c25df6a16111b915aaafbb4d49af7f63e0f89aac0e28022017bf14660bd51195ed62dd8349a1bae2395f684feaffe6090d9c1e5dcee41a1c","dilithium":{"type":"Buffer","data":[1,2,3]},"kyber":"a1b2c3"}"#;
+    const TS_PUBLIC_KEY: &str = "04989c0b76cb563971fdc9bef31ec06c3560f3249d6ee9e5d83c57625596e05f6f631f4d05b3ae518776ee08755a7703e64b2ebc32547504de0b55a142d4ecdf80";
+
+    fn ts_vote() -> SignedVote {
+        SignedVote {
+            voter: "voter".to_string(),
+            target_chain_id: "chain-a".to_string(),
+            timestamp: 1_700_000_000_000,
+            signature: TS_SIGNATURE.to_string(),
+            public_key: TS_PUBLIC_KEY.to_string(),
+        }
+    }
+
+    fn with_component(vote: &SignedVote, name: &str, value: serde_json::Value) -> SignedVote {
+        let mut signature: serde_json::Value = serde_json::from_str(&vote.signature).unwrap();
+        signature[name] = value;
+        SignedVote {
+            signature: signature.to_string(),
+            ..vote.clone()
+        }
+    }
+
+    #[test]
+    fn verifies_hybrid_crypto_output() {
+        let valid = ts_vote();
+
+        let mut wrong_message = valid.clone();
+        wrong_message.timestamp += 1;
+
+        let mut wrong_key = valid.clone();
+        let other = SigningKey::from_slice(&[9u8; 32]).unwrap();
+        wrong_key.public_key = hex::encode(other.verifying_key().to_sec1_bytes());
+
+        let mut compressed_key = valid.clone();
+        let key = SigningKey::from_slice(&[7u8; 32]).unwrap();
+        compressed_key.public_key = hex::encode(key.verifying_key().to_encoded_point(true));
+
+        let missing_kyber = with_component(&valid, "kyber", serde_json::json!(""));
+        let missing_dilithium = with_component(&valid, "dilithium", serde_json::Value::Null);
+
+        let mut garbage = valid.clone();
+        garbage.signature = "not json".to_string();
+
+        let results = verify_votes(&[
+            valid,
+            compressed_key,
+            wrong_message,
+            wrong_key,
+            missing_kyber,
+            missing_dilithium,
+            garbage,
+        ]);
+        assert_eq!(results, vec![true, true, false, false, false, false, false]);
+    }
+
+    #[test]
+    fn accepts_high_s_ecdsa_signatures() {
+        let ecc_key = SigningKey::from_slice(&[3u8; 32]).unwrap();
+        let digest = Sha256::digest(b"chain:1");
+        let low: Signature = ecc_key.sign_prehash(&digest).unwrap();
+        let (r, s) = low.split_scalars();
+        let high = Signature::from_scalars(r, -*s).unwrap();
+
+        let public_key = hex::encode(ecc_key.verifying_key().to_sec1_bytes());
+        let high_hex = hex::encode(high.to_der().as_bytes());
+        assert!(verify_ecc("chain:1", &high_hex, &public_key));
+    }
+
+    #[test]
+    fn packs_bitmap_lsb_first() {
+        let valid = [
+            true, false, true, false, false, false, false, false, false, true,
+        ];
+        assert_eq!(to_bitmap(&valid), vec![0b0000_0101, 0b0000_0010]);
+        assert!(to_bitmap(&[]).is_empty());
+    }
+}
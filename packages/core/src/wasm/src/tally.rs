@@ -0,0 +1,93 @@
+//! The consensus layer's `VoteTally` (`blockchain/consensus/voting/util.ts`).
+
+use crate::duplicates::DuplicatePolicy;
+use crate::processor::ChunkResult;
+use serde::{Deserialize, Serialize};
+
+/// Serialized with the same camelCase field names as the TypeScript
+/// `VoteTally`, so it can be returned from `DirectVotingUtil.tallyVotes`
+/// as is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoteTally {
+    pub approved: u128,
+    pub rejected: u128,
+    /// Votes that contributed to the tally after the duplicate policy.
+    pub total_votes: usize,
+    pub unique_voters: usize,
+    /// `unique_voters / eligible_voters`, or 0 when nobody is eligible.
+    pub participation_rate: f64,
+    pub timestamp: u64,
+}
+
+impl VoteTally {
+    /// Builds the tally for a result produced under `policy` from `vote_count`
+    /// input votes. `timestamp` is supplied by the caller so that every node
+    /// tallying the same votes produces the same value.
+    pub fn from_result(
+        result: &ChunkResult,
+        policy: DuplicatePolicy,
+        vote_count: usize,
+        eligible_voters: u64,
+        timestamp: u64,
+    ) -> Self {
+        let unique_voters = result.voters.len();
+        // Every policy except `Sum` counts exactly one vote per voter.
+        let total_votes = match policy {
+            DuplicatePolicy::Sum => vote_count,
+            _ => unique_voters,
+        };
+        VoteTally {
+            approved: result.approved,
+            rejected: result.rejected,
+            total_votes,
+            unique_voters,
+            participation_rate: participation_rate(unique_voters, eligible_voters),
+            timestamp,
+        }
+    }
+}
+
+/// Share of eligible voters that voted. Both operands are exact integers, so
+/// the single IEEE division is identical on every platform.
+pub fn participation_rate(unique_voters: usize, eligible_voters: u64) -> f64 {
+    if eligible_voters == 0 {
+        0.0
+    } else {
+        unique_voters as f64 / eligible_voters as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(voters: &[&str]) -> ChunkResult {
+        ChunkResult {
+            approved: 7,
+            rejected: 3,
+            voters: voters.iter().map(|v| v.to_string()).collect(),
+            duplicates: Vec::new(),
+            details: None,
+        }
+    }
+
+    #[test]
+    fn counts_votes_by_policy() {
+        let result = result(&["a", "b"]);
+        let tally = VoteTally::from_result(&result, DuplicatePolicy::Sum, 3, 8, 42);
+        assert_eq!(tally.total_votes, 3);
+        assert_eq!(tally.unique_voters, 2);
+        assert_eq!(tally.participation_rate, 0.25);
+        assert_eq!(tally.timestamp, 42);
+
+        let tally = VoteTally::from_result(&result, DuplicatePolicy::FirstWins, 3, 8, 42);
+        assert_eq!(tally.total_votes, 2);
+    }
+
+    #[test]
+    fn no_eligible_voters_means_zero_participation() {
+        let tally = VoteTally::from_result(&result(&["a"]), DuplicatePolicy::Sum, 1, 0, 0);
+        assert_eq!(tally.participation_rate, 0.0);
+    }
+}
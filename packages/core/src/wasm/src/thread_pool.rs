@@ -0,0 +1,13 @@
+//! Web Worker pool backing rayon on `wasm32` (the `threads` feature).
+//!
+//! The pool comes from `wasm-bindgen-rayon`: `initThreadPool(n)` starts `n`
+//! workers on the module's shared memory and installs them as rayon's global
+//! pool. The module must be built with `+atomics,+bulk-memory` and a
+//! rebuilt standard library; `wasm-bindgen-rayon` fails to compile without
+//! atomics. See the README.
+//!
+//! Workers, and any thread that starts a parallel tally, block in
+//! `Atomics.wait`, which browsers forbid on the main thread. Tallies in this
+//! build must therefore run inside a Web Worker.
+
+pub use wasm_bindgen_rayon::init_thread_pool;
@@ -0,0 +1,506 @@
+//! Vote validity rules from `DirectVotingUtil._verifyVote`, applied to a
+//! whole chunk with a reason for every vote that is dropped.
+//!
+//! Signatures are checked separately by [`crate::signature`]; this pass
+//! covers the structural, age and validator-set rules that `_verifyVote`
+//! applies before it.
+
+use crate::amount::{Amount, AmountError};
+use crate::multichain::ChainVote;
+use crate::wasm::MAX_SAFE_INTEGER;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// `VOTING_CONSTANTS.MAX_VOTE_AGE`: votes older than a day are dropped.
+pub const MAX_VOTE_AGE: u64 = 86_400_000;
+
+/// `Vote.chainVoteData`. `forkHeight` and `amount` are kept as sent, so a
+/// missing or malformed value rejects only its own vote.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainVoteData {
+    #[serde(default)]
+    pub target_chain_id: String,
+    /// A non-negative integer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fork_height: Option<Value>,
+    /// An amount string, or an integer such as a `bigint`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Value>,
+}
+
+/// A vote as collected from a voting period, before any checks. Fields
+/// that `_verifyVote` treats as required may be missing, empty or of the
+/// wrong type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandidateVote {
+    #[serde(default)]
+    pub vote_id: String,
+    #[serde(default)]
+    pub voter: String,
+    #[serde(default)]
+    pub signature: String,
+    /// Milliseconds since the epoch; `0` counts as missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_vote_data: Option<ChainVoteData>,
+}
+
+impl CandidateVote {
+    /// The chain vote to tally, once the vote has been accepted.
+    pub fn chain_vote(&self) -> Option<ChainVote> {
+        let data = self.chain_vote_data.as_ref()?;
+        Some(ChainVote {
+            voter: self.voter.clone(),
+            target_chain_id: data.target_chain_id.clone(),
+            fork_height: data.fork_height().ok()?,
+            amount: data.amount().ok()?.to_string(),
+            timestamp: Some(self.timestamp().ok()?),
+        })
+    }
+
+    fn timestamp(&self) -> Result<u64, RejectionReason> {
+        match integer_field(self.timestamp.as_ref()) {
+            None | Some(Some(0)) => Err(RejectionReason::MissingTimestamp),
+            Some(None) => Err(RejectionReason::InvalidTimestamp),
+            Some(Some(timestamp)) => Ok(timestamp),
+        }
+    }
+}
+
+impl ChainVoteData {
+    fn fork_height(&self) -> Result<u64, RejectionReason> {
+        match integer_field(self.fork_height.as_ref()) {
+            None => Err(RejectionReason::MissingForkHeight),
+            Some(None) => Err(RejectionReason::InvalidForkHeight),
+            Some(Some(height)) => Ok(height),
+        }
+    }
+
+    fn amount(&self) -> Result<Amount, RejectionReason> {
+        let parsed = match &self.amount {
+            None | Some(Value::Null) => return Err(RejectionReason::MissingAmount),
+            Some(Value::String(amount)) => amount.parse(),
+            // Integers, including `bigint`s, in their decimal form.
+            Some(Value::Number(n)) if n.is_i64() || n.is_u64() => n.to_string().parse(),
+            Some(_) => Err(AmountError::InvalidDigit),
+        };
+        parsed.map_err(|error| RejectionReason::InvalidAmount { error })
+    }
+}
+
+/// `None` when the field is missing or null, `Some(None)` when it is not a
+/// non-negative safe integer.
+fn integer_field(value: Option<&Value>) -> Option<Option<u64>> {
+    match value? {
+        Value::Null => None,
+        Value::Number(n) => Some(n.as_u64().or_else(|| {
+            n.as_f64()
+                .filter(|f| f.fract() == 0.0 && (0.0..=MAX_SAFE_INTEGER).contains(f))
+                .map(|f| f as u64)
+        })),
+        _ => Some(None),
+    }
+}
+
+/// Why a vote was dropped. Serialized as `{ "reason": "...", ... }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum RejectionReason {
+    /// The vote is not an object of the expected shape.
+    Malformed {
+        error: String,
+    },
+    MissingChainVoteData,
+    MissingSignature,
+    MissingVoter,
+    MissingTimestamp,
+    /// `timestamp` is not a non-negative integer.
+    InvalidTimestamp,
+    MissingTargetChain,
+    MissingForkHeight,
+    /// `chainVoteData.forkHeight` is not a non-negative integer.
+    InvalidForkHeight,
+    MissingAmount,
+    /// `chainVoteData.amount` is not a canonical amount within supply.
+    InvalidAmount {
+        error: AmountError,
+    },
+    /// Older than the maximum vote age; `age` is in milliseconds.
+    Expired {
+        age: u64,
+    },
+    /// The voter is not in the validator set.
+    UnknownValidator,
+    /// The voter is a validator but not active.
+    InactiveValidator,
+}
+
+/// The parts of a `Validator` the rules need.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidatorStatus {
+    pub address: String,
+    pub is_active: bool,
+}
+
+/// Validators keyed by address, like `validatorMap` in `collectVotes`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidatorSet {
+    active: HashMap<String, bool>,
+}
+
+impl ValidatorSet {
+    /// Later entries for the same address replace earlier ones, as with
+    /// `new Map(...)`.
+    pub fn new(validators: impl IntoIterator<Item = ValidatorStatus>) -> Self {
+        ValidatorSet {
+            active: validators
+                .into_iter()
+                .map(|validator| (validator.address, validator.is_active))
+                .collect(),
+        }
+    }
+
+    /// `None` when `address` is not a validator.
+    pub fn is_active(&self, address: &str) -> Option<bool> {
+        self.active.get(address).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RejectedVote {
+    pub index: usize,
+    pub voter: String,
+    #[serde(flatten)]
+    pub reason: RejectionReason,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    /// Votes that passed every rule, in input order.
+    pub accepted: Vec<CandidateVote>,
+    /// One entry per dropped vote, in input order.
+    pub rejected: Vec<RejectedVote>,
+}
+
+/// Applies the rules to one vote at time `now` (milliseconds), in the order
+/// `_verifyVote` checks them, and returns the first one it breaks.
+pub fn check_vote(
+    vote: &CandidateVote,
+    validators: &ValidatorSet,
+    now: u64,
+    max_vote_age: u64,
+) -> Result<(), RejectionReason> {
+    let Some(chain_vote_data) = &vote.chain_vote_data else {
+        return Err(RejectionReason::MissingChainVoteData);
+    };
+    if vote.signature.is_empty() {
+        return Err(RejectionReason::MissingSignature);
+    }
+    if vote.voter.is_empty() {
+        return Err(RejectionReason::MissingVoter);
+    }
+    let timestamp = vote.timestamp()?;
+    if chain_vote_data.target_chain_id.is_empty() {
+        return Err(RejectionReason::MissingTargetChain);
+    }
+    chain_vote_data.fork_height()?;
+    chain_vote_data.amount()?;
+
+    // Votes stamped in the future have no age, as in `_verifyVote`.
+    let age = now.saturating_sub(timestamp);
+    if age > max_vote_age {
+        return Err(RejectionReason::Expired { age });
+    }
+
+    match validators.is_active(&vote.voter) {
+        None => Err(RejectionReason::UnknownValidator),
+        Some(false) => Err(RejectionReason::InactiveValidator),
+        Some(true) => Ok(()),
+    }
+}
+
+/// Splits `votes` into accepted votes and rejections at time `now`,
+/// using [`MAX_VOTE_AGE`].
+pub fn validate_votes(
+    votes: &[CandidateVote],
+    validators: &ValidatorSet,
+    now: u64,
+) -> ValidationReport {
+    split_votes(votes, |vote| (&vote.voter, Ok(vote)), validators, now)
+}
+
+/// Like [`validate_votes`] for votes still in JSON form, as received from
+/// JS. A vote that does not even read as a [`CandidateVote`], such as one
+/// whose `voter` is a number, is rejected as `malformed` instead of failing
+/// the whole chunk.
+pub fn validate_values(votes: Vec<Value>, validators: &ValidatorSet, now: u64) -> ValidationReport {
+    let read: Vec<(String, Result<CandidateVote, RejectionReason>)> = votes
+        .into_iter()
+        .map(|value| {
+            let voter = value
+                .get("voter")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let voter = voter.to_string();
+            let vote = serde_json::from_value(value).map_err(|e| RejectionReason::Malformed {
+                error: e.to_string(),
+            });
+            (voter, vote)
+        })
+        .collect();
+    split_votes(
+        &read,
+        |(voter, vote)| (voter, vote.as_ref().map_err(Clone::clone)),
+        validators,
+        now,
+    )
+}
+
+/// `read` gives each vote's voter, for the report, and the vote itself.
+fn split_votes<T: Sync>(
+    votes: &[T],
+    read: impl Fn(&T) -> (&str, Result<&CandidateVote, RejectionReason>) + Sync,
+    validators: &ValidatorSet,
+    now: u64,
+) -> ValidationReport {
+    let outcomes: Vec<Result<(), RejectionReason>> = votes
+        .par_iter()
+        .map(|vote| check_vote(read(vote).1?, validators, now, MAX_VOTE_AGE))
+        .collect();
+
+    let mut report = ValidationReport {
+        accepted: Vec::new(),
+        rejected: Vec::new(),
+    };
+    for (index, (vote, outcome)) in votes.iter().zip(outcomes).enumerate() {
+        let (voter, vote) = read(vote);
+        match outcome {
+            Ok(()) => report.accepted.extend(vote.ok().cloned()),
+            Err(reason) => report.rejected.push(RejectedVote {
+                index,
+                voter: voter.to_string(),
+                reason,
+            }),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn vote(voter: &str, timestamp: u64) -> CandidateVote {
+        CandidateVote {
+            vote_id: format!("id-{voter}"),
+            voter: voter.to_string(),
+            signature: "sig".to_string(),
+            timestamp: Some(timestamp.into()),
+            chain_vote_data: Some(ChainVoteData {
+                target_chain_id: "chain-a".to_string(),
+                fork_height: Some(10.into()),
+                amount: Some("5".into()),
+            }),
+        }
+    }
+
+    fn validators() -> ValidatorSet {
+        ValidatorSet::new([
+            ValidatorStatus {
+                address: "active".to_string(),
+                is_active: true,
+            },
+            ValidatorStatus {
+                address: "inactive".to_string(),
+                is_active: false,
+            },
+        ])
+    }
+
+    fn reason(vote: &CandidateVote) -> Option<RejectionReason> {
+        check_vote(vote, &validators(), NOW, MAX_VOTE_AGE).err()
+    }
+
+    #[test]
+    fn reports_missing_fields_in_verify_vote_order() {
+        let mut v = vote("", 0);
+        v.signature.clear();
+        v.chain_vote_data = None;
+        assert_eq!(reason(&v), Some(RejectionReason::MissingChainVoteData));
+
+        v.chain_vote_data = vote("x", 1).chain_vote_data;
+        assert_eq!(reason(&v), Some(RejectionReason::MissingSignature));
+
+        v.signature = "sig".to_string();
+        assert_eq!(reason(&v), Some(RejectionReason::MissingVoter));
+
+        v.voter = "active".to_string();
+        assert_eq!(reason(&v), Some(RejectionReason::MissingTimestamp));
+
+        v.timestamp = Some(NOW.into());
+        v.chain_vote_data.as_mut().unwrap().target_chain_id.clear();
+        assert_eq!(reason(&v), Some(RejectionReason::MissingTargetChain));
+
+        let data = v.chain_vote_data.as_mut().unwrap();
+        data.target_chain_id = "chain-a".to_string();
+        data.amount = Some("1e300".into());
+        assert_eq!(
+            reason(&v),
+            Some(RejectionReason::InvalidAmount {
+                error: AmountError::InvalidDigit
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_fields_per_vote() {
+        let vote = |fields: Value| {
+            let mut vote = json!({
+                "voter": "active",
+                "signature": "sig",
+                "timestamp": NOW,
+                "chainVoteData": { "targetChainId": "chain-a", "forkHeight": 10, "amount": "5" },
+            });
+            for (key, value) in fields.as_object().unwrap() {
+                match key.strip_prefix("chainVoteData.") {
+                    Some(key) => vote["chainVoteData"][key] = value.clone(),
+                    None => vote[key] = value.clone(),
+                }
+            }
+            vote
+        };
+        let votes = vec![
+            vote(json!({})),
+            vote(json!({ "chainVoteData.forkHeight": null })),
+            vote(json!({ "chainVoteData.forkHeight": 1.5 })),
+            vote(json!({ "chainVoteData.amount": 5 })),
+            vote(json!({ "chainVoteData.amount": -5 })),
+            vote(json!({ "chainVoteData.amount": 5.5 })),
+            vote(json!({ "chainVoteData.amount": null })),
+            vote(json!({ "timestamp": "soon" })),
+            vote(json!({ "timestamp": -1 })),
+            vote(json!({ "signature": 7 })),
+        ];
+        let report = validate_values(votes, &validators(), NOW);
+
+        assert_eq!(report.accepted.len(), 2);
+        assert_eq!(
+            report.accepted[1].chain_vote().unwrap().amount,
+            "5",
+            "integer amounts are accepted"
+        );
+        let rejected: Vec<_> = report
+            .rejected
+            .iter()
+            .map(|r| (r.index, r.voter.as_str(), r.reason.clone()))
+            .collect();
+        let invalid_amount = |error| RejectionReason::InvalidAmount { error };
+        assert_eq!(
+            rejected[..rejected.len() - 1],
+            [
+                (1, "active", RejectionReason::MissingForkHeight),
+                (2, "active", RejectionReason::InvalidForkHeight),
+                (4, "active", invalid_amount(AmountError::Negative)),
+                (5, "active", invalid_amount(AmountError::InvalidDigit)),
+                (6, "active", RejectionReason::MissingAmount),
+                (7, "active", RejectionReason::InvalidTimestamp),
+                (8, "active", RejectionReason::InvalidTimestamp),
+            ]
+        );
+        let (index, voter, reason) = rejected.last().unwrap();
+        assert_eq!((*index, *voter), (9, "active"));
+        assert!(matches!(reason, RejectionReason::Malformed { .. }));
+    }
+
+    #[test]
+    fn enforces_max_vote_age() {
+        assert_eq!(reason(&vote("active", NOW - MAX_VOTE_AGE)), None);
+        assert_eq!(
+            reason(&vote("active", NOW - MAX_VOTE_AGE - 1)),
+            Some(RejectionReason::Expired {
+                age: MAX_VOTE_AGE + 1
+            })
+        );
+        assert_eq!(reason(&vote("active", NOW + 5_000)), None);
+    }
+
+    #[test]
+    fn requires_an_active_validator() {
+        assert_eq!(
+            reason(&vote("stranger", NOW)),
+            Some(RejectionReason::UnknownValidator)
+        );
+        assert_eq!(
+            reason(&vote("inactive", NOW)),
+            Some(RejectionReason::InactiveValidator)
+        );
+    }
+
+    #[test]
+    fn splits_a_chunk() {
+        let votes = vec![
+            vote("active", NOW),
+            vote("inactive", NOW),
+            vote("active", NOW - MAX_VOTE_AGE - 10),
+        ];
+        let report = validate_votes(&votes, &validators(), NOW);
+
+        assert_eq!(report.accepted, vec![votes[0].clone()]);
+        let rejected: Vec<_> = report
+            .rejected
+            .iter()
+            .map(|r| (r.index, r.reason.clone()))
+            .collect();
+        assert_eq!(
+            rejected,
+            vec![
+                (1, RejectionReason::InactiveValidator),
+                (
+                    2,
+                    RejectionReason::Expired {
+                        age: MAX_VOTE_AGE + 10
+                    }
+                ),
+            ]
+        );
+
+        let chain_vote = report.accepted[0].chain_vote().unwrap();
+        assert_eq!(chain_vote.target_chain_id, "chain-a");
+        assert_eq!(chain_vote.timestamp, Some(NOW));
+    }
+
+    #[test]
+    fn serializes_amount_errors() {
+        let reason = RejectionReason::InvalidAmount {
+            error: AmountError::ExceedsMaxSupply,
+        };
+        assert_eq!(
+            serde_json::to_string(&reason).unwrap(),
+            r#"{"reason":"invalid-amount","error":"exceeds-max-supply"}"#
+        );
+    }
+
+    #[test]
+    fn serializes_reason_inline() {
+        let rejected = RejectedVote {
+            index: 3,
+            voter: "v".to_string(),
+            reason: RejectionReason::Expired { age: 7 },
+        };
+        assert_eq!(
+            serde_json::to_string(&rejected).unwrap(),
+            r#"{"index":3,"voter":"v","reason":"expired","age":7}"#
+        );
+    }
+}
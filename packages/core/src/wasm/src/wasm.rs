@@ -0,0 +1,609 @@
+use crate::accumulator::VoteTallyAccumulator;
+use crate::consensus;
+use crate::decision::{self, ChainDecision, ForkDecision, Threshold};
+use crate::error::VoteError;
+use crate::memory;
+use crate::merkle::{self, MerkleProof, MerkleTree, MerkleVote};
+use crate::multichain::{ChainVote, MultiChainTally};
+use crate::parallelism;
+use crate::period::{
+    CompetingChains, FinalizedPeriod, PeriodEvent, PeriodSchedule, PeriodVote, VotingPeriod,
+};
+use crate::power;
+use crate::processor::{VoteData, VoteProcessor};
+use crate::quota::QuotaLimits;
+use crate::signature::{self, SignedVote};
+use crate::tally::VoteTally;
+use crate::validation::{self, ValidatorSet, ValidatorStatus};
+use crate::weighting::{AgeRules, VoterAge, Weighting};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(module = "/js/errors.js")]
+extern "C" {
+    type VoteProcessorError;
+
+    #[wasm_bindgen(constructor)]
+    fn new(
+        message: &str,
+        code: &str,
+        index: Option<usize>,
+        voter: Option<String>,
+    ) -> VoteProcessorError;
+}
+
+/// Errors reach JS as a `VoteProcessorError` (an `Error` subclass) with
+/// `code`, `index` and `voter` properties.
+impl From<VoteError> for JsValue {
+    fn from(error: VoteError) -> Self {
+        VoteProcessorError::new(
+            &error.to_string(),
+            error.code(),
+            error.index(),
+            error.voter().map(String::from),
+        )
+        .into()
+    }
+}
+
+/// Converts an argument from JS, reporting values of the wrong shape as
+/// `INVALID_ARGUMENT`.
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, VoteError> {
+    serde_wasm_bindgen::from_value(value).map_err(|e| VoteError::InvalidArgument(e.to_string()))
+}
+
+/// `Number.MAX_SAFE_INTEGER`; larger numbers may not be the integer the
+/// caller meant.
+pub(crate) const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// Converts a JS number argument such as a height or a time in milliseconds,
+/// reporting anything but a safe non-negative integer as `INVALID_ARGUMENT`
+/// rather than truncating it.
+pub(crate) fn integer_arg(name: &str, value: f64) -> Result<u64, VoteError> {
+    if value.fract() != 0.0 || !(0.0..=MAX_SAFE_INTEGER).contains(&value) {
+        return Err(VoteError::InvalidArgument(format!(
+            "{name} must be a non-negative safe integer, got {value}"
+        )));
+    }
+    Ok(value as u64)
+}
+
+/// Converts a result to JS. `u128` values become `BigInt`s and maps become
+/// plain objects, matching the `Record<string, string>` fields in TypeScript.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+    Ok(value.serialize(&serializer)?)
+}
+
+#[wasm_bindgen]
+pub struct WasmVoteProcessor {
+    inner: VoteProcessor,
+}
+
+impl Default for WasmVoteProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl WasmVoteProcessor {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        WasmVoteProcessor {
+            inner: VoteProcessor::new(),
+        }
+    }
+
+    /// Selects how votes are weighted: `"balance"` (default) or `"quadratic"`.
+    #[wasm_bindgen]
+    pub fn set_tally_mode(&mut self, mode: &str) -> Result<(), JsValue> {
+        self.inner.set_tally_mode(mode.parse()?);
+        Ok(())
+    }
+
+    /// Selects how voters with several votes in a chunk are handled:
+    /// `"reject"`, `"first-wins"` (default), `"last-wins"` or `"sum"`.
+    #[wasm_bindgen]
+    pub fn set_duplicate_policy(&mut self, policy: &str) -> Result<(), JsValue> {
+        self.inner.set_duplicate_policy(policy.parse()?);
+        Ok(())
+    }
+
+    /// Maximum number of votes accepted per chunk.
+    #[wasm_bindgen]
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        self.inner.set_chunk_size(chunk_size);
+    }
+
+    /// When on, results carry `details`: `{ voter, voteCount, balance,
+    /// power, choice }` for every voter, sorted like `voters`.
+    #[wasm_bindgen]
+    pub fn set_detailed(&mut self, detailed: bool) {
+        self.inner.set_detailed(detailed);
+    }
+
+    /// Weights every vote's power by age: `rules` is `{ curve,
+    /// minAccountAge, minCoinAge }` with `curve` one of `{ kind: "none" }`,
+    /// `{ kind: "exponential", numerator, denominator, stepMs }` or
+    /// `{ kind: "linear", zeroAtMs }`; `ages` is `{ voter, accountAge?,
+    /// coinAge? }[]`; votes are aged against `now`. Voters with no age or
+    /// too young an account or coins add no power.
+    #[wasm_bindgen]
+    pub fn set_weighting(
+        &mut self,
+        rules_js: JsValue,
+        ages_js: JsValue,
+        now: f64,
+    ) -> Result<(), JsValue> {
+        let rules: AgeRules = from_js(rules_js)?;
+        let ages: Vec<VoterAge> = from_js(ages_js)?;
+        let weighting = Weighting::new(rules, integer_arg("now", now)?, ages)?;
+        self.inner.set_weighting(Some(weighting));
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn clear_weighting(&mut self) {
+        self.inner.set_weighting(None);
+    }
+
+    /// Starts a running tally that keeps totals and voters across chunks,
+    /// using this processor's current settings.
+    #[wasm_bindgen]
+    pub fn create_accumulator(&self) -> WasmVoteTallyAccumulator {
+        WasmVoteTallyAccumulator {
+            inner: self.inner.accumulator(),
+        }
+    }
+
+    /// Starts an active voting period of `VOTING_PERIOD_BLOCKS` blocks and
+    /// `VOTING_PERIOD_MS` milliseconds from `start_height` and `start_time`,
+    /// tallied with this processor's current settings. The ID, height and
+    /// time must be non-negative integers.
+    #[wasm_bindgen]
+    pub fn start_period(
+        &self,
+        period_id: f64,
+        start_height: f64,
+        start_time: f64,
+        eligible_voters: u32,
+    ) -> Result<WasmVotingPeriod, JsValue> {
+        let schedule = PeriodSchedule::starting_at(
+            integer_arg("startHeight", start_height)?,
+            integer_arg("startTime", start_time)?,
+        );
+        Ok(WasmVotingPeriod {
+            inner: VotingPeriod::new(
+                integer_arg("periodId", period_id)?,
+                schedule,
+                &self.inner,
+                eligible_voters.into(),
+            ),
+        })
+    }
+
+    /// Starts the chain-selection period for a fork at `fork_height`, as
+    /// `initializeChainVotingPeriod` does, with `now` as its start time.
+    /// The period ID is derived from the fork, so every node gets the same
+    /// one. Throws `FORK_DEPTH_EXCEEDED` when the fork is more than
+    /// `MAX_FORK_DEPTH` blocks below `current_height`, and
+    /// `INVALID_ARGUMENT` when a height or `now` is not a non-negative
+    /// integer.
+    #[wasm_bindgen]
+    pub fn start_chain_period(
+        &self,
+        old_chain_id: String,
+        new_chain_id: String,
+        fork_height: f64,
+        current_height: f64,
+        eligible_voters: u32,
+        now: f64,
+    ) -> Result<WasmVotingPeriod, JsValue> {
+        let chains = CompetingChains {
+            old_chain_id,
+            new_chain_id,
+            common_ancestor_height: integer_arg("forkHeight", fork_height)?,
+        };
+        let now = integer_arg("now", now)?;
+        let clock = || now;
+        let inner = VotingPeriod::for_fork(
+            chains,
+            integer_arg("currentHeight", current_height)?,
+            &self.inner,
+            eligible_voters.into(),
+            &clock,
+        )?;
+        Ok(WasmVotingPeriod { inner })
+    }
+
+    /// Canonical quadratic voting power of a balance, clamped to
+    /// `MIN_VOTING_POWER`/`MAX_VOTING_POWER`.
+    #[wasm_bindgen]
+    pub fn quadratic_voting_power(balance: u128) -> u128 {
+        power::quadratic_power(balance)
+    }
+
+    #[wasm_bindgen]
+    pub fn process_vote_chunk(&self, votes_js: JsValue) -> Result<JsValue, JsValue> {
+        // Parse input votes
+        let votes: Vec<VoteData> = from_js(votes_js)?;
+
+        let result = self.inner.process_chunk(&votes)?;
+
+        to_js(&result)
+    }
+
+    /// Tallies a chunk into the full `VoteTally` shape used by
+    /// `DirectVotingUtil.tallyVotes`. `timestamp` is the tally time in
+    /// milliseconds, supplied by the caller so results are reproducible.
+    #[wasm_bindgen]
+    pub fn tally_votes(
+        &self,
+        votes_js: JsValue,
+        eligible_voters: u32,
+        timestamp: f64,
+    ) -> Result<JsValue, JsValue> {
+        let votes: Vec<VoteData> = from_js(votes_js)?;
+
+        let tally = self.inner.tally_votes(
+            &votes,
+            eligible_voters.into(),
+            integer_arg("timestamp", timestamp)?,
+        )?;
+
+        to_js(&tally)
+    }
+
+    /// Tallies `ChainVote`s (`{ voter, targetChainId, forkHeight, amount,
+    /// timestamp? }`) for the fork at `fork_height`, returning per-chain
+    /// power and voter counts, the winner, and the winner's `forkDecision`
+    /// (or `undefined` when nobody voted). Throws `INVALID_ARGUMENT` when
+    /// `fork_height` or `decided_at` is not a non-negative integer.
+    #[wasm_bindgen]
+    pub fn tally_chains(
+        &self,
+        votes_js: JsValue,
+        fork_height: f64,
+        decided_at: f64,
+    ) -> Result<JsValue, JsValue> {
+        let votes: Vec<ChainVote> = from_js(votes_js)?;
+        let fork_height = integer_arg("forkHeight", fork_height)?;
+        let decided_at = integer_arg("decidedAt", decided_at)?;
+
+        let tally = self.inner.tally_chains(&votes, fork_height)?;
+
+        let fork_decision = tally.fork_decision(decided_at);
+        to_js(&MultiChainSelection {
+            tally,
+            fork_decision,
+        })
+    }
+
+    /// Tallies votes packed by `serializeVotes` in `vote-processor.ts`,
+    /// avoiding the per-object serde conversion of `process_vote_chunk`.
+    #[wasm_bindgen]
+    pub fn process_vote_bytes(&self, data: &[u8]) -> Result<JsValue, JsValue> {
+        let result = self.inner.process_bytes(data)?;
+
+        to_js(&result)
+    }
+
+    /// Tallies `len` bytes of votes that JS wrote at `ptr` in wasm memory
+    /// (see `allocate`), in the `serializeVotes` layout, without copying
+    /// them out. Returns a pointer to a 48-byte result (`RawTally` in
+    /// `memory.rs`) that the caller frees with `deallocate(ptr, 48)`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to `len` bytes written by the caller.
+    #[wasm_bindgen]
+    pub unsafe fn process_votes_at(&self, ptr: *const u8, len: usize) -> Result<*mut u8, JsValue> {
+        Ok(memory::process_votes_at(&self.inner, ptr, len)?)
+    }
+}
+
+/// Allocates `size` bytes in wasm memory for JS to write into. Returns 0
+/// when `size` is 0 or allocation fails.
+#[wasm_bindgen]
+pub fn allocate(size: usize) -> *mut u8 {
+    memory::allocate(size)
+}
+
+/// Frees a buffer from `allocate` or `process_votes_at`.
+///
+/// # Safety
+///
+/// `ptr` must be 0 or come from `allocate` (or `process_votes_at`) with the
+/// same `size`, and must not be used afterwards.
+#[wasm_bindgen]
+pub unsafe fn deallocate(ptr: *mut u8, size: usize) {
+    memory::deallocate(ptr, size)
+}
+
+#[wasm_bindgen]
+pub struct WasmVoteTallyAccumulator {
+    inner: VoteTallyAccumulator,
+}
+
+#[wasm_bindgen]
+impl WasmVoteTallyAccumulator {
+    #[wasm_bindgen]
+    pub fn add_chunk(&mut self, votes_js: JsValue) -> Result<(), JsValue> {
+        let votes: Vec<VoteData> = from_js(votes_js)?;
+        self.inner.add_chunk(&votes).map_err(JsValue::from)
+    }
+
+    #[wasm_bindgen]
+    pub fn add_chunk_bytes(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        self.inner.add_bytes(data).map_err(JsValue::from)
+    }
+
+    /// Merges `other` into this accumulator. `other` is consumed and must not
+    /// be used afterwards.
+    #[wasm_bindgen]
+    pub fn merge(&mut self, other: WasmVoteTallyAccumulator) -> Result<(), JsValue> {
+        self.inner.merge(other.inner).map_err(JsValue::from)
+    }
+
+    #[wasm_bindgen]
+    pub fn snapshot(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.inner.snapshot())
+    }
+
+    /// Current totals as a `VoteTally`; see `WasmVoteProcessor::tally_votes`.
+    #[wasm_bindgen]
+    pub fn tally(&self, eligible_voters: u32, timestamp: f64) -> Result<JsValue, JsValue> {
+        let tally = self
+            .inner
+            .tally(eligible_voters.into(), integer_arg("timestamp", timestamp)?);
+        to_js(&tally)
+    }
+
+    /// Returns the final tally and frees the accumulator.
+    #[wasm_bindgen]
+    pub fn finalize(self) -> Result<JsValue, JsValue> {
+        to_js(&self.inner.finalize())
+    }
+}
+
+/// A voting period driven by block and time events. Each event method
+/// returns the finalized period (`{ periodId, status, startHeight,
+/// endHeight, startTime, endTime, tally, votesMerkleRoot }`) when the event
+/// ends the period, and `undefined` otherwise.
+#[wasm_bindgen]
+pub struct WasmVotingPeriod {
+    inner: VotingPeriod,
+}
+
+fn finalized_to_js(finalized: Option<FinalizedPeriod>) -> Result<JsValue, JsValue> {
+    match finalized {
+        Some(finalized) => to_js(&finalized),
+        None => Ok(JsValue::UNDEFINED),
+    }
+}
+
+#[wasm_bindgen]
+impl WasmVotingPeriod {
+    #[wasm_bindgen]
+    pub fn period_id(&self) -> f64 {
+        self.inner.period_id() as f64
+    }
+
+    /// `"active"`, `"completed"` or `"cancelled"`.
+    #[wasm_bindgen]
+    pub fn status(&self) -> String {
+        self.inner.status().to_string()
+    }
+
+    /// Adds `{ voteId, voter, balance, approved, timestamp, height? }`
+    /// votes and returns `{ index, voter, reason, ... }` for each vote left
+    /// out for being over quota. Throws `INACTIVE_PERIOD` once the period
+    /// has ended.
+    #[wasm_bindgen]
+    pub fn add_votes(&mut self, votes_js: JsValue) -> Result<JsValue, JsValue> {
+        let votes: Vec<PeriodVote> = from_js(votes_js)?;
+        to_js(&self.inner.add_votes(&votes)?)
+    }
+
+    /// Replaces the quota limits with `{ maxVotesPerWindow,
+    /// maxVotesPerPeriod, windowMs, cooldownBlocks }`.
+    #[wasm_bindgen]
+    pub fn set_quota_limits(&mut self, limits_js: JsValue) -> Result<(), JsValue> {
+        let limits: QuotaLimits = from_js(limits_js)?;
+        self.inner.set_quota_limits(limits);
+        Ok(())
+    }
+
+    /// `{ voter, periodVotes, windowStart, windowVotes, lastHeight }` for
+    /// every voter with an admitted vote, sorted by voter.
+    #[wasm_bindgen]
+    pub fn quota_usage(&self) -> Result<JsValue, JsValue> {
+        to_js(&self.inner.quota_usage())
+    }
+
+    #[wasm_bindgen]
+    pub fn on_block(&mut self, height: f64) -> Result<JsValue, JsValue> {
+        let event = PeriodEvent::Block {
+            height: integer_arg("height", height)?,
+        };
+        finalized_to_js(self.inner.apply(event)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn on_time(&mut self, timestamp: f64) -> Result<JsValue, JsValue> {
+        let event = PeriodEvent::Time {
+            timestamp: integer_arg("timestamp", timestamp)?,
+        };
+        finalized_to_js(self.inner.apply(event)?)
+    }
+
+    #[wasm_bindgen]
+    pub fn cancel(&mut self) -> Result<JsValue, JsValue> {
+        finalized_to_js(self.inner.apply(PeriodEvent::Cancel)?)
+    }
+}
+
+/// Whether tallies run in parallel: `{ mode: "parallel" | "sequential",
+/// threads }`. The single-threaded wasm build is always `"sequential"`; in
+/// the `threads` build, call this only once `initThreadPool` has resolved.
+#[wasm_bindgen]
+pub fn execution_mode() -> Result<JsValue, JsValue> {
+    to_js(&parallelism::execution_info())
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChainSelection {
+    #[serde(flatten)]
+    decision: ChainDecision,
+    fork_decision: ForkDecision,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MultiChainSelection {
+    #[serde(flatten)]
+    tally: MultiChainTally,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fork_decision: Option<ForkDecision>,
+}
+
+/// Mirrors `DirectVotingUtil.processVotingResults`: selects `new_chain_id`
+/// when `approved / (approved + rejected)` reaches
+/// `threshold_numerator / threshold_denominator` (pass `67, 100` for
+/// `NODE_SELECTION_THRESHOLD`), compared exactly. Returns the decision with
+/// its `forkDecision` record. Throws `INVALID_ARGUMENT` when the chain IDs
+/// are equal, or `fork_height` or `decided_at` is not a non-negative
+/// integer.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn decide_chain(
+    tally_js: JsValue,
+    old_chain_id: &str,
+    new_chain_id: &str,
+    threshold_numerator: u32,
+    threshold_denominator: u32,
+    fork_height: f64,
+    decided_at: f64,
+) -> Result<JsValue, JsValue> {
+    let tally: VoteTally = from_js(tally_js)?;
+    let threshold = Threshold::new(threshold_numerator.into(), threshold_denominator.into())?;
+
+    let fork_height = integer_arg("forkHeight", fork_height)?;
+    let decided_at = integer_arg("decidedAt", decided_at)?;
+    let decision = decision::decide_chain(&tally, old_chain_id, new_chain_id, threshold)?;
+    let fork_decision = decision.fork_decision(fork_height, decided_at);
+    to_js(&ChainSelection {
+        decision,
+        fork_decision,
+    })
+}
+
+/// Scores a block for `consensusData`: `{ powScore, votingScore,
+/// participationRate, combined }` from the block's `difficulty`, the
+/// network difficulty and the current period's `VoteTally`. Each score is a
+/// `BigInt` in units of 10^-18, so `combined` compares exactly between
+/// competing blocks in `handleChainFork`; divide by `10n ** 18n` for the
+/// header's `number` fields. Difficulties must be non-negative integers.
+#[wasm_bindgen]
+pub fn consensus_scores(
+    difficulty: f64,
+    network_difficulty: f64,
+    tally_js: JsValue,
+    eligible_voters: u32,
+) -> Result<JsValue, JsValue> {
+    let tally: VoteTally = from_js(tally_js)?;
+    let scores = consensus::consensus_scores(
+        integer_arg("difficulty", difficulty)?,
+        integer_arg("networkDifficulty", network_difficulty)?,
+        &tally,
+        eligible_voters.into(),
+    )?;
+    to_js(&scores)
+}
+
+/// Verifies the hybrid signatures of a batch of `SignedVote`s in parallel,
+/// each over `"${targetChainId}:${timestamp}"`. Returns a bitmap with bit
+/// `i % 8` of byte `i / 8` set when vote `i` is valid, so invalid votes can
+/// be dropped before `process_vote_chunk`.
+#[wasm_bindgen]
+pub fn verify_vote_signatures(votes_js: JsValue) -> Result<Vec<u8>, JsValue> {
+    let votes: Vec<SignedVote> = from_js(votes_js)?;
+    Ok(signature::to_bitmap(&signature::verify_votes(&votes)))
+}
+
+/// `votesMerkleRoot` over `{ voteId, voter, timestamp }` votes, built like
+/// `DirectVoting.createVoteMerkleRoot`.
+#[wasm_bindgen]
+pub fn votes_merkle_root(votes_js: JsValue) -> Result<String, JsValue> {
+    let votes: Vec<MerkleVote> = from_js(votes_js)?;
+    merkle::votes_merkle_root(&votes).map_err(JsValue::from)
+}
+
+/// `MerkleProof { index, hash, siblings }` that the vote at `index` is
+/// included in the `votesMerkleRoot` of `votes`.
+#[wasm_bindgen]
+pub fn vote_merkle_proof(votes_js: JsValue, index: usize) -> Result<JsValue, JsValue> {
+    let votes: Vec<MerkleVote> = from_js(votes_js)?;
+    let proof = MerkleTree::from_votes(&votes).and_then(|tree| tree.proof(index))?;
+    to_js(&proof)
+}
+
+/// Whether `proof` shows that `vote` is included under `root`.
+#[wasm_bindgen]
+pub fn verify_vote_merkle_proof(
+    proof_js: JsValue,
+    vote_js: JsValue,
+    root: &str,
+) -> Result<bool, JsValue> {
+    let proof: MerkleProof = from_js(proof_js)?;
+    let vote: MerkleVote = from_js(vote_js)?;
+    Ok(merkle::verify_proof(&proof, &vote.leaf_data(), root))
+}
+
+/// Applies the `_verifyVote` rules (required fields, `MAX_VOTE_AGE`, active
+/// validator) to `votes` at time `now` in milliseconds. `validators` are
+/// `{ address, isActive }` records. Returns `{ accepted, rejected }`, with
+/// an `{ index, voter, reason }` entry for every dropped vote, including
+/// votes with missing or malformed fields. Throws `INVALID_ARGUMENT` when
+/// `votes` is not an array or `now` is not a non-negative integer.
+#[wasm_bindgen]
+pub fn validate_votes(
+    votes_js: JsValue,
+    validators_js: JsValue,
+    now: f64,
+) -> Result<JsValue, JsValue> {
+    let votes: Vec<serde_json::Value> = from_js(votes_js)?;
+    let validators: Vec<ValidatorStatus> = from_js(validators_js)?;
+    let now = integer_arg("now", now)?;
+    let report = validation::validate_values(votes, &ValidatorSet::new(validators), now);
+    to_js(&report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_args_must_be_safe_non_negative_integers() {
+        assert_eq!(integer_arg("now", 0.0).unwrap(), 0);
+        assert_eq!(integer_arg("now", MAX_SAFE_INTEGER).unwrap(), (1 << 53) - 1);
+        for value in [-1.0, 1.5, f64::NAN, f64::INFINITY, MAX_SAFE_INTEGER + 1.0] {
+            let err = integer_arg("now", value).unwrap_err();
+            assert_eq!(err.code(), "INVALID_ARGUMENT", "{value}");
+        }
+    }
+
+    #[test]
+    fn fork_heights_are_not_truncated_to_u32() {
+        let height = f64::from(u32::MAX) + 5.0;
+        assert_eq!(integer_arg("forkHeight", height).unwrap(), (1 << 32) + 4);
+        for value in [-1.0, 10.5] {
+            let err = integer_arg("forkHeight", value).unwrap_err();
+            assert_eq!(err.code(), "INVALID_ARGUMENT", "{value}");
+            assert!(err.to_string().contains("forkHeight"), "{err}");
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Vote power decay and account-age weighting, from `VOTING_CONSTANTS`.
+//!
+//! A [`Weighting`] set on the processor (see
+//! [`VoteProcessor::set_weighting`]) discounts each vote's power by how old
+//! the vote is at the weighting's reference time, and gives no power to
+//! voters whose account or coins are younger than the [`AgeRules`] allow.
+//! Voters without a known age are treated as immature, so coins moved just
+//! before a chain-selection vote cannot swing it. All arithmetic is integer,
+//! so every node weights the same votes identically.
+//!
+//! [`VoteProcessor::set_weighting`]: crate::processor::VoteProcessor::set_weighting
+
+use crate::error::VoteError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Fractional bits of the fixed-point factor in [`DecayCurve::Exponential`].
+const FRACTION_BITS: u32 = 64;
+
+/// `VOTING_CONSTANTS.VOTE_POWER_DECAY` (0.5) as a fraction.
+pub const VOTE_POWER_DECAY: (u64, u64) = (1, 2);
+/// `VOTING_CONSTANTS.MIN_ACCOUNT_AGE`: about a week of 5-minute blocks.
+pub const MIN_ACCOUNT_AGE: u64 = 10_080;
+/// `VOTING_CONSTANTS.MATURITY_PERIOD`: one day, in milliseconds.
+pub const MATURITY_PERIOD: u64 = 86_400_000;
+
+/// How a vote's power falls with its age. Serialized as
+/// `{ "kind": "...", ... }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "kebab-case",
+    rename_all_fields = "camelCase"
+)]
+pub enum DecayCurve {
+    /// Power does not decay.
+    None,
+    /// Power is multiplied by `numerator / denominator` once for every full
+    /// `step_ms` of vote age. The factor is raised to the number of steps in
+    /// 64-bit binary fixed point, rounding down, so the cost does not grow
+    /// with the vote's age.
+    Exponential {
+        numerator: u64,
+        denominator: u64,
+        step_ms: u64,
+    },
+    /// Power falls in a straight line to zero at `zero_at_ms` of vote age.
+    Linear { zero_at_ms: u64 },
+}
+
+impl Default for DecayCurve {
+    /// Halves power for every [`MATURITY_PERIOD`] of vote age.
+    fn default() -> Self {
+        DecayCurve::Exponential {
+            numerator: VOTE_POWER_DECAY.0,
+            denominator: VOTE_POWER_DECAY.1,
+            step_ms: MATURITY_PERIOD,
+        }
+    }
+}
+
+impl DecayCurve {
+    fn check(&self) -> Result<(), VoteError> {
+        let invalid = |message: &str| Err(VoteError::InvalidArgument(message.to_string()));
+        match *self {
+            DecayCurve::Exponential {
+                numerator,
+                denominator,
+                step_ms,
+            } => {
+                if denominator == 0 || numerator > denominator {
+                    return invalid("Decay factor must be a fraction between 0 and 1");
+                }
+                if step_ms == 0 {
+                    return invalid("Decay step must be at least 1 ms");
+                }
+                Ok(())
+            }
+            DecayCurve::Linear { zero_at_ms: 0 } => invalid("Linear decay needs a non-zero span"),
+            _ => Ok(()),
+        }
+    }
+
+    /// `power` after `age_ms` of decay.
+    pub fn apply(&self, power: u128, age_ms: u64) -> u128 {
+        match *self {
+            DecayCurve::None => power,
+            DecayCurve::Exponential {
+                numerator,
+                denominator,
+                step_ms,
+            } => {
+                if numerator == denominator {
+                    return power;
+                }
+                // `factor` is below `1 << FRACTION_BITS`, so every product of
+                // two factors fits in `u128`.
+                let mut base = (u128::from(numerator) << FRACTION_BITS) / u128::from(denominator);
+                let mut factor = 1 << FRACTION_BITS;
+                let mut steps = age_ms / step_ms;
+                while steps > 0 && factor > 0 {
+                    if steps & 1 == 1 {
+                        factor = (factor * base) >> FRACTION_BITS;
+                    }
+                    base = (base * base) >> FRACTION_BITS;
+                    steps >>= 1;
+                }
+                // Splitting `power` keeps the product within `u128`.
+                (power >> FRACTION_BITS) * factor
+                    + (((power & ((1 << FRACTION_BITS) - 1)) * factor) >> FRACTION_BITS)
+            }
+            DecayCurve::Linear { zero_at_ms } => match zero_at_ms.checked_sub(age_ms) {
+                Some(left) if left > 0 => {
+                    let (left, span) = (u128::from(left), u128::from(zero_at_ms));
+                    power / span * left + power % span * left / span
+                }
+                _ => 0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgeRules {
+    pub curve: DecayCurve,
+    /// Youngest account, in blocks, whose votes carry power.
+    pub min_account_age: u64,
+    /// Youngest coins, in milliseconds, whose votes carry power.
+    pub min_coin_age: u64,
+}
+
+impl Default for AgeRules {
+    fn default() -> Self {
+        AgeRules {
+            curve: DecayCurve::default(),
+            min_account_age: MIN_ACCOUNT_AGE,
+            min_coin_age: MATURITY_PERIOD,
+        }
+    }
+}
+
+/// A voter's age at the reference time. Either may be unknown; a voter is
+/// mature when every known age meets its minimum and at least one is known.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VoterAge {
+    pub voter: String,
+    /// Blocks since the account first appeared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_age: Option<u64>,
+    /// Milliseconds since the voted coins last moved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coin_age: Option<u64>,
+}
+
+/// Age rules, the voters' ages and the time votes are aged against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Weighting {
+    rules: AgeRules,
+    /// Milliseconds; a vote's age is `now - timestamp`.
+    now: u64,
+    ages: HashMap<String, (Option<u64>, Option<u64>)>,
+}
+
+impl Weighting {
+    /// Later entries for the same voter replace earlier ones. Fails with
+    /// `INVALID_ARGUMENT` for a decay curve that could raise power or never
+    /// advances.
+    pub fn new(
+        rules: AgeRules,
+        now: u64,
+        ages: impl IntoIterator<Item = VoterAge>,
+    ) -> Result<Self, VoteError> {
+        rules.curve.check()?;
+        Ok(Weighting {
+            rules,
+            now,
+            ages: ages
+                .into_iter()
+                .map(|age| (age.voter, (age.account_age, age.coin_age)))
+                .collect(),
+        })
+    }
+
+    pub fn rules(&self) -> AgeRules {
+        self.rules
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Whether `voter`'s votes carry power.
+    pub fn is_mature(&self, voter: &str) -> bool {
+        match self.ages.get(voter) {
+            None | Some((None, None)) => false,
+            Some(&(account_age, coin_age)) => {
+                account_age.is_none_or(|age| age >= self.rules.min_account_age)
+                    && coin_age.is_none_or(|age| age >= self.rules.min_coin_age)
+            }
+        }
+    }
+
+    /// The power a vote from `voter` cast at `timestamp` keeps. Votes
+    /// without a timestamp, or stamped after `now`, have no age.
+    pub fn apply(&self, power: u128, voter: &str, timestamp: Option<u64>) -> u128 {
+        if !self.is_mature(voter) {
+            return 0;
+        }
+        let age = timestamp.map_or(0, |timestamp| self.now.saturating_sub(timestamp));
+        self.rules.curve.apply(power, age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = MATURITY_PERIOD;
+
+    fn age(voter: &str, account_age: Option<u64>, coin_age: Option<u64>) -> VoterAge {
+        VoterAge {
+            voter: voter.to_string(),
+            account_age,
+            coin_age,
+        }
+    }
+
+    #[test]
+    fn default_curve_halves_per_maturity_period() {
+        let curve = DecayCurve::default();
+        assert_eq!(curve.apply(1_000, 0), 1_000);
+        assert_eq!(curve.apply(1_000, DAY - 1), 1_000);
+        assert_eq!(curve.apply(1_000, DAY), 500);
+        assert_eq!(curve.apply(1_000, 3 * DAY), 125);
+        assert_eq!(curve.apply(1_000, 1_000 * DAY), 0);
+        assert_eq!(curve.apply(u128::MAX, DAY), u128::MAX / 2);
+    }
+
+    #[test]
+    fn exponential_decay_is_bounded_in_age() {
+        let slow = DecayCurve::Exponential {
+            numerator: 999_999,
+            denominator: 1_000_000,
+            step_ms: 1,
+        };
+        assert_eq!(slow.apply(u128::MAX, u64::MAX), 0);
+        assert!(slow.apply(1_000_000, 1_000) < 1_000_000);
+        assert!(slow.apply(1_000_000, 1_000) > 998_000);
+
+        let third = DecayCurve::Exponential {
+            numerator: 2,
+            denominator: 3,
+            step_ms: DAY,
+        };
+        assert_eq!(third.apply(9_000, 2 * DAY), 3_999);
+        assert_eq!(third.apply(0, u64::MAX), 0);
+    }
+
+    #[test]
+    fn linear_and_no_decay() {
+        let curve = DecayCurve::Linear {
+            zero_at_ms: 4 * DAY,
+        };
+        assert_eq!(curve.apply(1_000, DAY), 750);
+        assert_eq!(curve.apply(1_000, 4 * DAY), 0);
+        assert_eq!(curve.apply(u128::MAX, 0), u128::MAX);
+        assert_eq!(DecayCurve::None.apply(7, u64::MAX), 7);
+    }
+
+    #[test]
+    fn rejects_curves_that_grow_or_stall() {
+        let rules = |curve| AgeRules {
+            curve,
+            ..AgeRules::default()
+        };
+        let exponential = |numerator, denominator, step_ms| DecayCurve::Exponential {
+            numerator,
+            denominator,
+            step_ms,
+        };
+        for curve in [
+            exponential(3, 2, DAY),
+            exponential(1, 0, DAY),
+            exponential(1, 2, 0),
+            DecayCurve::Linear { zero_at_ms: 0 },
+        ] {
+            let err = Weighting::new(rules(curve), 0, []).unwrap_err();
+            assert_eq!(err.code(), "INVALID_ARGUMENT");
+        }
+        assert!(Weighting::new(rules(exponential(1, 1, 1)), 0, []).is_ok());
+    }
+
+    #[test]
+    fn immature_and_unknown_voters_carry_no_power() {
+        let now = 10 * DAY;
+        let weighting = Weighting::new(
+            AgeRules::default(),
+            now,
+            [
+                age("old", Some(MIN_ACCOUNT_AGE), Some(DAY)),
+                age("new-account", Some(MIN_ACCOUNT_AGE - 1), None),
+                age("moved-coins", Some(50_000), Some(DAY - 1)),
+                age("coins-only", None, Some(30 * DAY)),
+                age("no-age", None, None),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(weighting.apply(800, "old", Some(now)), 800);
+        assert_eq!(weighting.apply(800, "old", Some(now - 2 * DAY)), 200);
+        assert_eq!(weighting.apply(800, "old", Some(now + DAY)), 800);
+        assert_eq!(weighting.apply(800, "coins-only", None), 800);
+        for voter in ["new-account", "moved-coins", "no-age", "unknown"] {
+            assert_eq!(weighting.apply(800, voter, Some(now)), 0, "{voter}");
+        }
+    }
+
+    #[test]
+    fn serializes_rules() {
+        assert_eq!(
+            serde_json::to_string(&AgeRules::default()).unwrap(),
+            r#"{"curve":{"kind":"exponential","numerator":1,"denominator":2,"stepMs":86400000},"minAccountAge":10080,"minCoinAge":86400000}"#
+        );
+    }
+}
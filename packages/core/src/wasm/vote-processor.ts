@@ -1,10 +1,37 @@
-class WasmError extends Error {
+/**
+ * Error thrown by the Rust module (`VoteProcessorError` in js/errors.js).
+ * `code` is stable; `index` and `voter` identify the failing vote.
+ */
+export interface VoteProcessorError extends Error {
+  code: string;
+  index?: number;
+  voter?: string;
+}
+
+function isVoteProcessorError(error: unknown): error is VoteProcessorError {
+  return (
+    error instanceof Error &&
+    typeof (error as Partial<VoteProcessorError>).code === 'string'
+  );
+}
+
+export class WasmError extends Error {
+  /** Code from the Rust module, e.g. `PARSE_ERROR` or `DUPLICATE_VOTE`. */
+  public readonly code?: string;
+  public readonly index?: number;
+  public readonly voter?: string;
+
   constructor(
     message: string,
     public readonly cause?: unknown,
   ) {
     super(message);
     this.name = 'WasmError';
+    if (isVoteProcessorError(cause)) {
+      this.code = cause.code;
+      this.index = cause.index;
+      this.voter = cause.voter;
+    }
   }
 }
 
@@ -12,27 +39,81 @@ export interface VoteData {
   balance: string;
   approved: number;
   voter: string;
+  timestamp?: number;
+}
+
+export interface DuplicateVoter {
+  voter: string;
+  indices: number[];
+}
+
+/** Per-voter breakdown, returned when detailed mode is on. */
+export interface VoterDetail {
+  voter: string;
+  /** Votes cast, whether the duplicate policy counted them or not. */
+  voteCount: number;
+  /** Sum of the balances of the counted votes. */
+  balance: bigint;
+  power: bigint;
+  choice: 'approve' | 'reject' | 'split';
 }
 
 interface ChunkResult {
   approved: bigint;
   rejected: bigint;
+  /** Sorted, so results compare equal across runs and nodes. */
   voters: string[];
+  duplicates: DuplicateVoter[];
+  details?: VoterDetail[];
 }
 
 interface WasmVoteResult {
   approved: bigint;
   rejected: bigint;
   voters: string[];
+  duplicates: DuplicateVoter[];
+  details?: VoterDetail[];
 }
 
-interface WasmExports {
-  process_vote_chunk: (data: Uint8Array) => WasmVoteResult;
+/** The `wasm-pack build --target web` glue in `pkg/`, see README.md. */
+interface WasmGlue {
+  default: () => Promise<unknown>;
+  WasmVoteProcessor: new () => {
+    process_vote_bytes(data: Uint8Array): WasmVoteResult;
+    free(): void;
+  };
+}
+
+/** The Node-API build (`cargo build --features node`), see README.md. */
+interface NativeAddon {
+  VoteProcessor: new () => {
+    processVoteBytes(data: Buffer): WasmVoteResult;
+  };
+}
+
+const NATIVE_ADDON_PATH = './vote_processor.node';
+const WASM_GLUE_PATH = './pkg/vote_processor.js';
+
+/**
+ * Loads the native addon when running under Node.js. Returns null in
+ * browsers, or when the addon has not been built, so callers can fall back
+ * to wasm.
+ */
+function loadNativeAddon(): NativeAddon | null {
+  if (typeof process === 'undefined' || !process.versions?.node) {
+    return null;
+  }
+  try {
+    // eslint-disable-next-line @typescript-eslint/no-require-imports
+    return require(NATIVE_ADDON_PATH) as NativeAddon;
+  } catch {
+    return null;
+  }
 }
 
 export class WasmVoteProcessor {
-  private wasmModule: WebAssembly.Instance | null = null;
-  private memory: WebAssembly.Memory | null = null;
+  private wasm: InstanceType<WasmGlue['WasmVoteProcessor']> | null = null;
+  private native: InstanceType<NativeAddon['VoteProcessor']> | null = null;
   private readonly MAX_CHUNK_SIZE = 1000;
   private initialized = false;
 
@@ -49,33 +130,19 @@ export class WasmVoteProcessor {
   }
 
   private async initWasm(): Promise<void> {
-    try {
-      // Create a WebAssembly memory instance.
-      const memory = new WebAssembly.Memory({ initial: 10, maximum: 100 });
-      const importObject = {
-        env: {
-          memory,
-          abort: () => console.error('Wasm aborted'),
-        },
-      };
-
-      let wasmModule;
-      try {
-        // Attempt to instantiate via streaming
-        wasmModule = await WebAssembly.instantiateStreaming(
-          fetch('./pkg/vote_processor_bg.wasm'),
-          importObject,
-        );
-      } catch (streamingError) {
-        // Fallback: instantiate from ArrayBuffer if streaming fails
-        console.warn('instantiateStreaming failed, falling back to ArrayBuffer instantiation', streamingError);
-        const response = await fetch('./pkg/vote_processor_bg.wasm');
-        const buffer = await response.arrayBuffer();
-        wasmModule = await WebAssembly.instantiate(buffer, importObject);
-      }
+    const addon = loadNativeAddon();
+    if (addon) {
+      this.native = new addon.VoteProcessor();
+      this.initialized = true;
+      return;
+    }
 
-      this.wasmModule = wasmModule.instance;
-      this.memory = memory;
+    try {
+      // The glue instantiates `vote_processor_bg.wasm` with the imports it
+      // expects and converts arguments and results across the boundary.
+      const glue = (await import(WASM_GLUE_PATH)) as WasmGlue;
+      await glue.default();
+      this.wasm = new glue.WasmVoteProcessor();
       this.initialized = true;
     } catch (error) {
       this.initialized = false;
@@ -87,7 +154,7 @@ export class WasmVoteProcessor {
    * Processes a chunk of votes by serializing the data, passing it to WASM, and deserializing the result.
    */
   public async processVoteChunk(votes: VoteData[]): Promise<ChunkResult> {
-    if (!this.initialized || !this.wasmModule) {
+    if (!this.initialized || (!this.wasm && !this.native)) {
       throw new WasmError('WASM module not initialized');
     }
 
@@ -103,16 +170,26 @@ export class WasmVoteProcessor {
         approved: 0n,
         rejected: 0n,
         voters: [],
+        duplicates: [],
       };
     }
 
     try {
       const serializedVotes = this.serializeVotes(votes);
-      const wasmExports = this.wasmModule.exports as unknown as WasmExports;
-      if (typeof wasmExports.process_vote_chunk !== 'function') {
-        throw new WasmError('WASM export "process_vote_chunk" is missing');
+      if (this.native) {
+        const result = this.native.processVoteBytes(
+          Buffer.from(
+            serializedVotes.buffer,
+            serializedVotes.byteOffset,
+            serializedVotes.byteLength,
+          ),
+        );
+        return this.deserializeResult(result);
+      }
+      if (!this.wasm) {
+        throw new WasmError('WASM module not initialized');
       }
-      const result = wasmExports.process_vote_chunk(serializedVotes);
+      const result = this.wasm.process_vote_bytes(serializedVotes);
       return this.deserializeResult(result);
     } catch (error) {
       throw new WasmError('Vote processing failed', error);
@@ -174,15 +251,18 @@ export class WasmVoteProcessor {
       approved: rawResult.approved,
       rejected: rawResult.rejected,
       voters: Array.from(rawResult.voters),
+      duplicates: Array.from(rawResult.duplicates),
+      ...(rawResult.details && { details: Array.from(rawResult.details) }),
     };
   }
 
   /**
-   * Disposes of the WASM module and its memory.
+   * Frees the Rust processor behind the wasm module.
    */
   public async dispose(): Promise<void> {
-    this.wasmModule = null;
-    this.memory = null;
+    this.wasm?.free();
+    this.wasm = null;
+    this.native = null;
     this.initialized = false;
   }
 }